@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Claims of the gaia access tokens, gaia issues them and the client reads
+/// them back.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub iss: String,
+    /// `JWT_AUDIENCE`, checked by the services verifying the tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    pub sub: String,
+    pub exp: i64,
+    pub iat: i64,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    /// Scoped tokens only grant access to gaia itself, e.g. MFA enrollment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// `users.token_version` when the token was issued, tokens with an older
+    /// version are rejected.
+    #[serde(default)]
+    pub ver: i32,
+    /// Session the token was issued for, revoking it rejects the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    /// Organization of the user, tokens without it belong to the system
+    /// organization.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+}
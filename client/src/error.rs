@@ -0,0 +1,52 @@
+use std::fmt;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+/// Why a request was refused, responds like the gaia service does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// There is no bearer token.
+    MissingToken,
+    /// The token is expired, malformed or not signed by gaia.
+    InvalidToken(String),
+    /// The user does not have the permission.
+    Forbidden(String),
+    /// The key set could not be fetched.
+    KeysUnavailable(String),
+}
+
+impl AuthError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AuthError::MissingToken | AuthError::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            AuthError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AuthError::KeysUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "missing bearer token"),
+            AuthError::InvalidToken(reason) => write!(f, "{}", reason),
+            AuthError::Forbidden(permission) => write!(f, "permission {} is required", permission),
+            AuthError::KeysUnavailable(reason) => {
+                write!(f, "failed to fetch the signing keys: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
@@ -0,0 +1,36 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+
+use crate::{AuthError, AuthUser, Verifier};
+
+/// Returns the token of the `Authorization: Bearer` header.
+pub(crate) fn bearer(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+/// Extracts the user of the bearer token, the one verified by `AuthLayer` if
+/// the route has it.
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    Verifier: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+        let token = bearer(&parts.headers).ok_or(AuthError::MissingToken)?;
+        Verifier::from_ref(state).verify(token).await
+    }
+}
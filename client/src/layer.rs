@@ -0,0 +1,193 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::Request,
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{extract::bearer, AuthError, AuthUser, Verifier};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// Refuses the requests without a valid bearer token, the user is added to
+/// the request extensions for `AuthUser` and `require_permission`.
+#[derive(Clone)]
+pub struct AuthLayer {
+    verifier: Verifier,
+}
+
+impl AuthLayer {
+    pub fn new(verifier: Verifier) -> Self {
+        AuthLayer { verifier }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            verifier: self.verifier.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    verifier: Verifier,
+}
+
+impl<S> Service<Request> for AuthService<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let verifier = self.verifier.clone();
+        // the ready service is used, the clone waits for the next request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let user = match bearer(request.headers()) {
+                Some(token) => verifier.verify(token).await,
+                None => Err(AuthError::MissingToken),
+            };
+            match user {
+                Ok(user) => {
+                    request.extensions_mut().insert(user);
+                    inner.call(request).await
+                }
+                Err(err) => Ok(err.into_response()),
+            }
+        })
+    }
+}
+
+/// Refuses the requests whose user does not have `permission`, with the same
+/// semantics as `Jwt::has_permission` in gaia. The routes must be behind an
+/// `AuthLayer`, e.g.
+///
+/// ```ignore
+/// Router::new()
+///     .route("/reports", get(reports))
+///     .route_layer(require_permission("report:read"))
+///     .layer(AuthLayer::new(verifier))
+/// ```
+pub fn require_permission(permission: &str) -> RequirePermissionLayer {
+    RequirePermissionLayer {
+        permission: Arc::from(permission),
+    }
+}
+
+#[derive(Clone)]
+pub struct RequirePermissionLayer {
+    permission: Arc<str>,
+}
+
+impl<S> Layer<S> for RequirePermissionLayer {
+    type Service = RequirePermission<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequirePermission {
+            inner,
+            permission: self.permission.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RequirePermission<S> {
+    inner: S,
+    permission: Arc<str>,
+}
+
+impl<S> Service<Request> for RequirePermission<S>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let allowed = match request.extensions().get::<AuthUser>() {
+            Some(user) => user.require(&self.permission),
+            None => Err(AuthError::MissingToken),
+        };
+        match allowed {
+            Ok(()) => Box::pin(self.inner.call(request)),
+            Err(err) => Box::pin(async move { Ok(err.into_response()) }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, routing::get, Extension, Router};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn user(permissions: &[&str]) -> AuthUser {
+        AuthUser {
+            id: Uuid::nil(),
+            permissions: permissions.iter().map(|p| String::from(*p)).collect(),
+            organization: Uuid::nil(),
+            session_id: None,
+        }
+    }
+
+    async fn status(user: Option<AuthUser>) -> StatusCode {
+        let mut app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .route_layer(require_permission("report:read"));
+        if let Some(user) = user {
+            app = app.layer(Extension(user));
+        }
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn require_permission_checks_the_grants() {
+        assert_eq!(status(Some(user(&["report:*"]))).await, StatusCode::OK);
+        assert_eq!(status(Some(user(&["admin"]))).await, StatusCode::OK);
+        assert_eq!(
+            status(Some(user(&["admin", "!report:read"]))).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(Some(user(&["user:read"]))).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn require_permission_needs_a_user() {
+        assert_eq!(status(None).await, StatusCode::UNAUTHORIZED);
+    }
+}
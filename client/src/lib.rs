@@ -0,0 +1,36 @@
+//! Verifies gaia access tokens in axum services.
+//!
+//! Tokens are verified with the keys gaia publishes at
+//! `/.well-known/jwks.json`, either per handler with the `AuthUser`
+//! extractor, which needs a `Verifier` in the state, or for whole routers
+//! with `AuthLayer`. `require_permission` guards routes with the same
+//! permission semantics as gaia.
+//!
+//! ```ignore
+//! let verifier = Verifier::from_env()?;
+//! let app = Router::new()
+//!     .route("/reports", get(reports))
+//!     .route_layer(require_permission("report:read"))
+//!     .layer(AuthLayer::new(verifier));
+//!
+//! async fn reports(user: AuthUser) -> String {
+//!     format!("reports of {}", user.id)
+//! }
+//! ```
+
+mod claims;
+mod error;
+mod extract;
+mod layer;
+mod user;
+mod verifier;
+
+pub mod permission;
+
+pub use claims::Claims;
+pub use error::AuthError;
+pub use layer::{
+    require_permission, AuthLayer, AuthService, RequirePermission, RequirePermissionLayer,
+};
+pub use user::AuthUser;
+pub use verifier::Verifier;
@@ -0,0 +1,200 @@
+//! Permission matching.
+//!
+//! Permissions are namespaced as `namespace:action`, e.g. `user:read`. A
+//! grant may use `*` for either part, `user:*` grants every action on users
+//! and `*:read` grants reading anything. Grants prefixed with `!` are deny
+//! entries and win over any grant, except for `root` which is always allowed.
+//! `admin`, `*` and `*:*` grant everything that is not denied.
+
+/// Prefix of deny entries.
+pub const DENY_PREFIX: char = '!';
+
+/// Permissions that grant everything.
+const SUPER_PERMISSIONS: &[&str] = &["root", "admin"];
+
+/// Returns true if `pattern` matches `permission`, `pattern` may contain
+/// wildcards but `permission` is taken literally.
+pub fn matches(pattern: &str, permission: &str) -> bool {
+    if pattern == "*" || pattern == permission {
+        return true;
+    }
+    let (Some((namespace, action)), Some((wanted_namespace, wanted_action))) =
+        (pattern.split_once(':'), permission.split_once(':'))
+    else {
+        return false;
+    };
+    (namespace == "*" || namespace == wanted_namespace)
+        && (action == "*" || action == wanted_action)
+}
+
+/// Why the grants allow or refuse a permission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason<'a> {
+    /// The grants hold `root`.
+    Root,
+    /// Refused by a deny entry.
+    Denied(&'a str),
+    /// Allowed by a grant.
+    Granted(&'a str),
+    /// No grant matches.
+    NotGranted,
+}
+
+impl Reason<'_> {
+    pub fn allowed(&self) -> bool {
+        matches!(self, Reason::Root | Reason::Granted(_))
+    }
+}
+
+impl std::fmt::Display for Reason<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Root => write!(f, "granted by root"),
+            Reason::Denied(grant) => write!(f, "denied by {}", grant),
+            Reason::Granted(grant) => write!(f, "granted by {}", grant),
+            Reason::NotGranted => write!(f, "not granted"),
+        }
+    }
+}
+
+/// Returns true if the grants allow `permission`.
+pub fn allows<S: AsRef<str>>(grants: &[S], permission: &str) -> bool {
+    check(grants, permission).allowed()
+}
+
+/// Same as `allows`, also telling which grant decided.
+pub fn check<'a, S: AsRef<str>>(grants: &'a [S], permission: &str) -> Reason<'a> {
+    let grants = grants.iter().map(|grant| grant.as_ref());
+    if grants.clone().any(|grant| grant == "root") {
+        return Reason::Root;
+    }
+    let mut reason = Reason::NotGranted;
+    for grant in grants {
+        match grant.strip_prefix(DENY_PREFIX) {
+            Some(denied) => {
+                if matches(denied, permission) {
+                    return Reason::Denied(grant);
+                }
+            }
+            None => {
+                if reason == Reason::NotGranted
+                    && (SUPER_PERMISSIONS.contains(&grant) || matches(grant, permission))
+                {
+                    reason = Reason::Granted(grant);
+                }
+            }
+        }
+    }
+    reason
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_exact_permission() {
+        assert!(matches("user:read", "user:read"));
+        assert!(!matches("user:read", "user:update"));
+        assert!(!matches("user:read", "group:read"));
+        assert!(matches("nobody", "nobody"));
+        assert!(!matches("nobody", "user:read"));
+    }
+
+    #[test]
+    fn matches_namespace_wildcard() {
+        assert!(matches("user:*", "user:read"));
+        assert!(matches("user:*", "user:update"));
+        assert!(!matches("user:*", "group:read"));
+        assert!(!matches("user:*", "user"));
+        assert!(!matches("user:*", "users:read"));
+    }
+
+    #[test]
+    fn matches_action_wildcard() {
+        assert!(matches("*:read", "user:read"));
+        assert!(matches("*:read", "group:read"));
+        assert!(!matches("*:read", "user:update"));
+        assert!(!matches("*:read", "read"));
+    }
+
+    #[test]
+    fn matches_everything() {
+        assert!(matches("*", "user:read"));
+        assert!(matches("*", "nobody"));
+        assert!(matches("*:*", "group:delete"));
+        assert!(!matches("*:*", "nobody"));
+    }
+
+    #[test]
+    fn wildcards_in_permission_are_literal() {
+        assert!(!matches("user:read", "user:*"));
+        assert!(!matches("user:read", "*:read"));
+        assert!(matches("user:*", "user:*"));
+    }
+
+    #[test]
+    fn matches_nested_actions() {
+        assert!(matches("user:*", "user:sessions:read"));
+        assert!(matches("user:sessions:read", "user:sessions:read"));
+        assert!(!matches("*:read", "user:sessions:read"));
+    }
+
+    #[test]
+    fn allows_granted_permissions() {
+        assert!(allows(&["user:read"], "user:read"));
+        assert!(allows(&["group:read", "user:*"], "user:update"));
+        assert!(allows(&["*:read"], "group:read"));
+        assert!(!allows(&["user:read"], "user:update"));
+        assert!(!allows::<&str>(&[], "user:read"));
+    }
+
+    #[test]
+    fn allows_super_permissions() {
+        assert!(allows(&["root"], "user:delete"));
+        assert!(allows(&["admin"], "group:update"));
+        assert!(allows(&["*"], "group:update"));
+        assert!(!allows(&["nobody"], "user:read"));
+    }
+
+    #[test]
+    fn deny_wins_over_grants() {
+        assert!(!allows(&["user:*", "!user:delete"], "user:delete"));
+        assert!(allows(&["user:*", "!user:delete"], "user:update"));
+        assert!(!allows(&["!user:delete", "user:*"], "user:delete"));
+        assert!(!allows(&["*:read", "!group:*"], "group:read"));
+        assert!(!allows(&["admin", "!user:*"], "user:read"));
+        assert!(!allows(&["*", "!*:delete"], "group:delete"));
+        assert!(!allows(&["!user:read"], "user:read"));
+    }
+
+    #[test]
+    fn root_ignores_deny() {
+        assert!(allows(&["root", "!user:*"], "user:read"));
+        assert!(allows(&["!*", "root"], "group:delete"));
+    }
+
+    #[test]
+    fn allows_owned_grants() {
+        let grants = vec![String::from("user:*"), String::from("!user:delete")];
+        assert!(allows(&grants, "user:read"));
+        assert!(!allows(&grants, "user:delete"));
+    }
+
+    #[test]
+    fn check_tells_the_deciding_grant() {
+        let grants = ["user:read", "user:*", "!user:delete"];
+        assert_eq!(check(&grants, "user:read"), Reason::Granted("user:read"));
+        assert_eq!(check(&grants, "user:update"), Reason::Granted("user:*"));
+        assert_eq!(
+            check(&grants, "user:delete"),
+            Reason::Denied("!user:delete")
+        );
+        assert_eq!(check(&grants, "group:read"), Reason::NotGranted);
+        assert_eq!(check(&["!*", "root"], "group:read"), Reason::Root);
+        assert_eq!(
+            check(&grants, "user:delete").to_string(),
+            "denied by !user:delete"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use uuid::Uuid;
+
+use crate::{permission, AuthError, Claims};
+
+/// The user of a verified access token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthUser {
+    pub id: Uuid,
+    pub permissions: Vec<String>,
+    /// Organization of the user, the nil id is the system organization.
+    pub organization: Uuid,
+    pub session_id: Option<Uuid>,
+}
+
+impl AuthUser {
+    /// Same as `Jwt::has_permission` in gaia, see `permission` for how grants
+    /// are matched.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        permission::allows(&self.permissions, permission)
+    }
+
+    /// Returns `AuthError::Forbidden` if the user does not have `permission`.
+    pub fn require(&self, permission: &str) -> Result<(), AuthError> {
+        if self.has_permission(permission) {
+            Ok(())
+        } else {
+            Err(AuthError::Forbidden(String::from(permission)))
+        }
+    }
+}
+
+impl TryFrom<Claims> for AuthUser {
+    type Error = AuthError;
+
+    fn try_from(claims: Claims) -> Result<Self, Self::Error> {
+        let invalid = |_| AuthError::InvalidToken(String::from("invalid token"));
+        Ok(AuthUser {
+            id: Uuid::parse_str(&claims.sub).map_err(invalid)?,
+            permissions: claims.groups,
+            organization: match claims.org {
+                Some(org) => Uuid::parse_str(&org).map_err(invalid)?,
+                None => Uuid::nil(),
+            },
+            session_id: claims
+                .sid
+                .map(|sid| Uuid::parse_str(&sid))
+                .transpose()
+                .map_err(invalid)?,
+        })
+    }
+}
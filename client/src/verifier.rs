@@ -0,0 +1,291 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use tokio::sync::RwLock;
+
+use crate::{AuthError, AuthUser, Claims};
+
+/// How long the keys are used before they are fetched again.
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Tokens signed by an unknown key fetch the keys again, but not more often
+/// than this, failed fetches are retried after the same delay.
+const MIN_REFRESH: Duration = Duration::from_secs(30);
+
+struct CachedKeys {
+    keys: JwkSet,
+    fetched_at: Instant,
+    /// Last attempt to fetch the keys, successful or not.
+    checked_at: Instant,
+}
+
+impl CachedKeys {
+    fn new(keys: JwkSet) -> Self {
+        let now = Instant::now();
+        CachedKeys {
+            keys,
+            fetched_at: now,
+            checked_at: now,
+        }
+    }
+
+    fn key(&self, kid: Option<&str>) -> Result<DecodingKey, AuthError> {
+        find(&self.keys, kid).map_or(Err(unknown_key()), decoding_key)
+    }
+}
+
+/// Verifies gaia access tokens with the keys published at
+/// `/.well-known/jwks.json`, the keys are cached and shared by the clones.
+///
+/// Tokens are verified locally, revoking a session or changing the groups of
+/// a user takes effect when the token expires. Services needing more use
+/// `POST /authorize` of gaia. When the keys can not be fetched again, the last
+/// ones keep being used until gaia is reachable.
+#[derive(Clone)]
+pub struct Verifier {
+    jwks_url: String,
+    issuer: String,
+    audience: Option<String>,
+    cache_ttl: Duration,
+    min_refresh: Duration,
+    http: reqwest::Client,
+    keys: Arc<RwLock<Option<CachedKeys>>>,
+}
+
+impl Verifier {
+    /// Verifier of the tokens issued by `issuer` (`JWT_ISSUER` of gaia) with
+    /// the keys at `jwks_url`.
+    pub fn new(jwks_url: impl Into<String>, issuer: impl Into<String>) -> Self {
+        Verifier {
+            jwks_url: jwks_url.into(),
+            issuer: issuer.into(),
+            audience: None,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            min_refresh: MIN_REFRESH,
+            http: reqwest::Client::new(),
+            keys: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Verifier configured by `GAIA_AUTH_URL`, the address of gaia,
+    /// `GAIA_AUTH_ISSUER` (`gaia` by default) and `GAIA_AUTH_AUDIENCE`.
+    pub fn from_env() -> Result<Self, std::env::VarError> {
+        let url = std::env::var("GAIA_AUTH_URL")?;
+        let issuer = std::env::var("GAIA_AUTH_ISSUER").unwrap_or(String::from("gaia"));
+        let jwks_url = format!("{}/.well-known/jwks.json", url.trim_end_matches('/'));
+        let verifier = Verifier::new(jwks_url, issuer);
+        Ok(match std::env::var("GAIA_AUTH_AUDIENCE") {
+            Ok(audience) => verifier.audience(audience),
+            Err(_) => verifier,
+        })
+    }
+
+    /// Only accepts tokens for `audience` (`JWT_AUDIENCE` of gaia), tokens
+    /// without an audience are accepted otherwise.
+    pub fn audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    pub fn cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Verifies the signature, expiration, issuer and audience of `token`.
+    pub async fn verify(&self, token: &str) -> Result<AuthUser, AuthError> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|err| AuthError::InvalidToken(err.to_string()))?;
+        let key = self.key(header.kid.as_deref()).await?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[&self.issuer]);
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = jsonwebtoken::decode::<Claims>(token, &key, &validation)
+            .map_err(|err| AuthError::InvalidToken(err.to_string()))?
+            .claims;
+        // scoped tokens only grant access to gaia itself, e.g. MFA enrollment
+        if claims.scope.is_some() {
+            return Err(AuthError::InvalidToken(String::from("invalid token scope")));
+        }
+        AuthUser::try_from(claims)
+    }
+
+    async fn key(&self, kid: Option<&str>) -> Result<DecodingKey, AuthError> {
+        if let Some(cached) = self.keys.read().await.as_ref() {
+            if cached.fetched_at.elapsed() < self.cache_ttl {
+                if let Some(jwk) = find(&cached.keys, kid) {
+                    return decoding_key(jwk);
+                }
+            }
+            if cached.checked_at.elapsed() < self.min_refresh {
+                return cached.key(kid);
+            }
+        }
+
+        let mut cached = self.keys.write().await;
+        // another request may have fetched the keys while waiting for the lock
+        if let Some(cached) = cached.as_ref() {
+            if cached.checked_at.elapsed() < self.min_refresh {
+                return cached.key(kid);
+            }
+        }
+        match (self.fetch().await, cached.as_mut()) {
+            (Ok(keys), _) => {
+                let keys = cached.insert(CachedKeys::new(keys));
+                keys.key(kid)
+            }
+            (Err(_), Some(stale)) => {
+                stale.checked_at = Instant::now();
+                stale.key(kid)
+            }
+            (Err(err), None) => Err(err),
+        }
+    }
+
+    async fn fetch(&self) -> Result<JwkSet, AuthError> {
+        let unavailable = |err: reqwest::Error| AuthError::KeysUnavailable(err.to_string());
+        self.http
+            .get(&self.jwks_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(unavailable)?
+            .json()
+            .await
+            .map_err(unavailable)
+    }
+}
+
+/// Tokens without a key id use the first key.
+fn find<'a>(keys: &'a JwkSet, kid: Option<&str>) -> Option<&'a Jwk> {
+    match kid {
+        Some(kid) => keys.find(kid),
+        None => keys.keys.first(),
+    }
+}
+
+fn decoding_key(jwk: &Jwk) -> Result<DecodingKey, AuthError> {
+    DecodingKey::from_jwk(jwk).map_err(|err| AuthError::InvalidToken(err.to_string()))
+}
+
+fn unknown_key() -> AuthError {
+    AuthError::InvalidToken(String::from("unknown signing key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{
+        jwk::{AlgorithmParameters, CommonParameters, RSAKeyParameters, RSAKeyType},
+        EncodingKey, Header,
+    };
+    use rsa::{pkcs1::EncodeRsaPrivateKey, traits::PublicKeyParts, RsaPrivateKey};
+    use uuid::Uuid;
+
+    use super::*;
+
+    struct Keys {
+        keys: JwkSet,
+        failing: AtomicBool,
+    }
+
+    async fn jwks(State(keys): State<Arc<Keys>>) -> Result<Json<JwkSet>, StatusCode> {
+        if keys.failing.load(Ordering::SeqCst) {
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        Ok(Json(keys.keys.clone()))
+    }
+
+    /// Serves the public key of `key` and returns the address of the key set.
+    async fn serve(key: &RsaPrivateKey) -> (String, Arc<Keys>) {
+        let jwk = Jwk {
+            common: CommonParameters {
+                key_id: Some(String::from("gaia")),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+            }),
+        };
+        let keys = Arc::new(Keys {
+            keys: JwkSet { keys: vec![jwk] },
+            failing: AtomicBool::new(false),
+        });
+        let app = Router::new()
+            .route("/.well-known/jwks.json", get(jwks))
+            .with_state(keys.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/.well-known/jwks.json",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, keys)
+    }
+
+    fn token(key: &RsaPrivateKey) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let claims = Claims {
+            iss: String::from("gaia"),
+            aud: None,
+            sub: Uuid::nil().to_string(),
+            exp: now + 60,
+            iat: now,
+            groups: vec![String::from("report:read")],
+            scope: None,
+            ver: 0,
+            sid: None,
+            org: None,
+        };
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(String::from("gaia"));
+        let pem = key.to_pkcs1_pem(Default::default()).unwrap();
+        let key = EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap();
+        jsonwebtoken::encode(&header, &claims, &key).unwrap()
+    }
+
+    #[tokio::test]
+    async fn keeps_the_last_keys_while_they_can_not_be_fetched() {
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let (url, keys) = serve(&key).await;
+        let mut verifier = Verifier::new(url, "gaia").cache_ttl(Duration::ZERO);
+        verifier.min_refresh = Duration::ZERO;
+        let token = token(&key);
+
+        keys.failing.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            verifier.verify(&token).await,
+            Err(AuthError::KeysUnavailable(_))
+        ));
+
+        keys.failing.store(false, Ordering::SeqCst);
+        let user = verifier.verify(&token).await.unwrap();
+        assert_eq!(user.permissions, vec![String::from("report:read")]);
+
+        // the keys are expired and every fetch fails from now on
+        keys.failing.store(true, Ordering::SeqCst);
+        assert_eq!(verifier.verify(&token).await, Ok(user.clone()));
+        assert_eq!(verifier.verify(&token).await, Ok(user));
+    }
+}
@@ -0,0 +1,2 @@
+drop table recovery_codes;
+alter table users drop column mfa_enabled_at;
\ No newline at end of file
@@ -0,0 +1,15 @@
+alter table users add column mfa_enabled_at bigint;
+--
+-- table recovery_codes
+--
+create table recovery_codes (
+id uuid primary key not null default gen_random_uuid(),
+user_id uuid not null,
+code_hash bytea not null,
+used_at bigint,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+foreign key (user_id) references users(id) on delete cascade
+);
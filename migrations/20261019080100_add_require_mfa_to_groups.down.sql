@@ -0,0 +1 @@
+alter table groups drop column require_mfa;
\ No newline at end of file
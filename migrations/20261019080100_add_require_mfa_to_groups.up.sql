@@ -0,0 +1,2 @@
+alter table groups add column require_mfa boolean not null default false;
+update groups set require_mfa = true where name in ('root', 'admin');
@@ -0,0 +1,3 @@
+drop table email_verifications;
+alter table users drop column pending_email;
+alter table users drop column email_verified_at;
\ No newline at end of file
@@ -0,0 +1,18 @@
+alter table users add column email_verified_at bigint;
+alter table users add column pending_email varchar(255);
+--
+-- table email_verifications
+--
+create table email_verifications (
+id uuid primary key not null default gen_random_uuid(),
+user_id uuid not null,
+email varchar(255) not null,
+token_hash bytea not null,
+expires_at bigint not null,
+used_at bigint,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+foreign key (user_id) references users(id) on delete cascade
+);
@@ -0,0 +1,2 @@
+drop table login_tokens;
+alter table groups drop column allow_passwordless;
\ No newline at end of file
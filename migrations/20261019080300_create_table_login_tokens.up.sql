@@ -0,0 +1,18 @@
+alter table groups add column allow_passwordless boolean not null default false;
+--
+-- table login_tokens
+--
+create table login_tokens (
+id uuid primary key not null default gen_random_uuid(),
+user_id uuid not null,
+method varchar(10) not null,
+token_hash bytea not null,
+attempts integer not null default 0,
+expires_at bigint not null,
+used_at bigint,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+foreign key (user_id) references users(id) on delete cascade
+);
@@ -0,0 +1 @@
+alter table users drop column registered_at;
\ No newline at end of file
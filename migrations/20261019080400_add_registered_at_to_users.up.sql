@@ -0,0 +1 @@
+alter table users add column registered_at bigint;
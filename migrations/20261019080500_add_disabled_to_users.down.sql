@@ -0,0 +1 @@
+alter table users drop column disabled;
\ No newline at end of file
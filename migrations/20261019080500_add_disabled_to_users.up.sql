@@ -0,0 +1,3 @@
+alter table users add column disabled boolean not null default false;
+-- locked now blocks login, the seeded users are protected by editable
+update users set locked = false where username in ('root', 'admin') and not editable;
@@ -0,0 +1,20 @@
+--
+-- table sessions
+--
+create table sessions (
+id uuid primary key not null default gen_random_uuid(),
+user_id uuid not null,
+device varchar(100),
+ip varchar(45),
+user_agent varchar(255),
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+last_seen_at bigint not null default extract(
+    epoch
+    from now()
+),
+revoked_at bigint,
+foreign key (user_id) references users(id) on delete cascade
+);
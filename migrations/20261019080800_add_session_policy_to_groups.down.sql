@@ -0,0 +1,3 @@
+alter table sessions drop column idle_timeout;
+alter table groups drop column max_sessions;
+alter table groups drop column idle_timeout;
\ No newline at end of file
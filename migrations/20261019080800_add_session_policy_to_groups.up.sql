@@ -0,0 +1,5 @@
+-- minutes of inactivity after which sessions expire
+alter table groups add column idle_timeout integer;
+-- maximum number of simultaneous sessions, the oldest ones are revoked
+alter table groups add column max_sessions integer;
+alter table sessions add column idle_timeout integer;
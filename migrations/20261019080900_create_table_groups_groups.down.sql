@@ -0,0 +1 @@
+drop table groups_groups;
\ No newline at end of file
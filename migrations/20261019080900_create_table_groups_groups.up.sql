@@ -0,0 +1,11 @@
+--
+-- pivot table groups_groups, members of member_id are members of group_id
+--
+create table groups_groups (
+group_id uuid not null,
+member_id uuid not null,
+primary key (group_id, member_id),
+check (group_id <> member_id),
+foreign key (group_id) references groups(id) on delete cascade,
+foreign key (member_id) references groups(id) on delete cascade
+);
\ No newline at end of file
@@ -0,0 +1,7 @@
+alter table groups add column permissions jsonb not null default '[]';
+update groups g set permissions = (
+    select jsonb_agg(gp.permission) from groups_permissions gp where gp.group_id = g.id
+)
+where exists (select 1 from groups_permissions gp where gp.group_id = g.id);
+drop table groups_permissions;
+drop table permissions;
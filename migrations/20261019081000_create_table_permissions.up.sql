@@ -0,0 +1,32 @@
+--
+-- table permissions, registered by the services checking them
+--
+create table permissions (
+name varchar(100) primary key not null,
+description varchar(255),
+service varchar(50) not null,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+updated_at bigint not null default extract(
+    epoch
+    from now()
+)
+);
+--
+-- pivot table groups_permissions, permission may be a wildcard or a deny entry
+--
+create table groups_permissions (
+group_id uuid not null,
+permission varchar(100) not null,
+primary key (group_id, permission),
+foreign key (group_id) references groups(id) on delete cascade
+);
+insert into groups_permissions (group_id, permission)
+select distinct id, jsonb_array_elements_text(permissions) from groups;
+insert into permissions (name, service)
+select distinct ltrim(permission, '!'), 'gaia' from groups_permissions
+where permission not like '%*%'
+on conflict do nothing;
+alter table groups drop column permissions;
@@ -0,0 +1 @@
+alter table users drop column attributes;
\ No newline at end of file
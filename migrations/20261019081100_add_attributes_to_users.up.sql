@@ -0,0 +1,2 @@
+-- attributes of the user checked by the policies, e.g. its department
+alter table users add column attributes jsonb not null default '{}';
\ No newline at end of file
@@ -0,0 +1 @@
+drop table policies;
\ No newline at end of file
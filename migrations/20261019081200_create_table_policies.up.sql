@@ -0,0 +1,16 @@
+--
+-- table policies, rules evaluated by the policy engine
+--
+create table policies (
+id uuid primary key not null default gen_random_uuid(),
+rule jsonb not null,
+enabled boolean not null default true,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+updated_at bigint not null default extract(
+    epoch
+    from now()
+)
+);
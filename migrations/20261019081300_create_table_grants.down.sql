@@ -0,0 +1 @@
+drop table grants;
\ No newline at end of file
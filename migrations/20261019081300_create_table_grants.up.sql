@@ -0,0 +1,19 @@
+--
+-- table grants, permissions on a single resource given to a user or a group
+--
+create table grants (
+id uuid primary key not null default gen_random_uuid(),
+user_id uuid,
+group_id uuid,
+permission varchar(100) not null,
+resource_type varchar(30) not null,
+resource_id uuid not null,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+check ((user_id is null) <> (group_id is null)),
+foreign key (user_id) references users(id) on delete cascade,
+foreign key (group_id) references groups(id) on delete cascade
+);
+create index grants_resource on grants (resource_type, resource_id);
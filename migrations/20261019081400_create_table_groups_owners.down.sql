@@ -0,0 +1 @@
+drop table groups_owners;
\ No newline at end of file
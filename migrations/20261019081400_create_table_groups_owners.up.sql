@@ -0,0 +1,14 @@
+--
+-- pivot table groups_owners, owners manage the members of the group
+--
+create table groups_owners (
+group_id uuid not null,
+user_id uuid not null,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+primary key (group_id, user_id),
+foreign key (group_id) references groups(id) on delete cascade,
+foreign key (user_id) references users(id) on delete cascade
+);
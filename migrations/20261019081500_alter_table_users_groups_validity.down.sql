@@ -0,0 +1,3 @@
+drop view active_users_groups;
+alter table users_groups drop column valid_until;
+alter table users_groups drop column valid_from;
@@ -0,0 +1,10 @@
+-- memberships may start and end at a given time, e.g. for contractors
+alter table users_groups add column valid_from bigint;
+alter table users_groups add column valid_until bigint;
+--
+-- view active_users_groups, the memberships valid at this time
+--
+create view active_users_groups as
+select * from users_groups
+where (valid_from is null or valid_from <= extract(epoch from now()))
+and (valid_until is null or valid_until > extract(epoch from now()));
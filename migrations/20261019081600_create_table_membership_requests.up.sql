@@ -0,0 +1,28 @@
+--
+-- table membership_requests, temporary memberships asked by users and
+-- approved or rejected by an owner of the group
+--
+create table membership_requests (
+id uuid primary key not null default gen_random_uuid(),
+user_id uuid not null,
+group_id uuid not null,
+reason text,
+valid_from bigint,
+valid_until bigint not null,
+status varchar(20) not null default 'pending',
+decided_by uuid,
+decided_at bigint,
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+updated_at bigint not null default extract(
+    epoch
+    from now()
+),
+foreign key (user_id) references users(id) on delete cascade,
+foreign key (group_id) references groups(id) on delete cascade,
+foreign key (decided_by) references users(id) on delete set null
+);
+create unique index membership_requests_pending on membership_requests (user_id, group_id)
+where status = 'pending';
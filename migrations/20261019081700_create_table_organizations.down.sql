@@ -0,0 +1,9 @@
+alter table groups drop constraint groups_org_name_key;
+alter table groups add constraint groups_name_key unique (name);
+alter table groups drop column org_id;
+alter table users drop constraint users_org_email_key;
+alter table users drop constraint users_org_username_key;
+alter table users add constraint users_email_key unique (email);
+alter table users add constraint users_username_key unique (username);
+alter table users drop column org_id;
+drop table organizations;
@@ -0,0 +1,33 @@
+--
+-- table organizations, the tenants users and groups belong to
+--
+create table organizations (
+id uuid primary key not null default gen_random_uuid(),
+name varchar(30) not null,
+description varchar(255),
+created_at bigint not null default extract(
+    epoch
+    from now()
+),
+updated_at bigint not null default extract(
+    epoch
+    from now()
+),
+unique (name)
+);
+-- the system organization, existing users and groups belong to it and its
+-- super-admins work across organizations
+insert into organizations (id, name, description)
+values ('00000000-0000-0000-0000-000000000000', 'default', 'system organization');
+alter table users add column org_id uuid not null default '00000000-0000-0000-0000-000000000000'
+references organizations(id) on delete cascade;
+alter table users alter column org_id drop default;
+alter table users drop constraint users_username_key;
+alter table users drop constraint users_email_key;
+alter table users add constraint users_org_username_key unique (org_id, username);
+alter table users add constraint users_org_email_key unique (org_id, email);
+alter table groups add column org_id uuid not null default '00000000-0000-0000-0000-000000000000'
+references organizations(id) on delete cascade;
+alter table groups alter column org_id drop default;
+alter table groups drop constraint groups_name_key;
+alter table groups add constraint groups_org_name_key unique (org_id, name);
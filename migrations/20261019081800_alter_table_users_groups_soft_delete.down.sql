@@ -0,0 +1,11 @@
+drop index groups_org_name_key;
+alter table groups add constraint groups_org_name_key unique (org_id, name);
+drop index users_org_email_key;
+drop index users_org_username_key;
+alter table users add constraint users_org_email_key unique (org_id, email);
+alter table users add constraint users_org_username_key unique (org_id, username);
+drop view active_groups_groups;
+create or replace view active_users_groups as
+select * from users_groups
+where (valid_from is null or valid_from <= extract(epoch from now()))
+and (valid_until is null or valid_until > extract(epoch from now()));
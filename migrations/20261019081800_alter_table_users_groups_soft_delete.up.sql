@@ -0,0 +1,22 @@
+-- deleted users and groups keep their rows until they are restored, the
+-- memberships of deleted users and groups are not active
+create or replace view active_users_groups as
+select * from users_groups
+where (valid_from is null or valid_from <= extract(epoch from now()))
+and (valid_until is null or valid_until > extract(epoch from now()))
+and user_id in (select id from users where deleted_at is null)
+and group_id in (select id from groups where deleted_at is null);
+--
+-- view active_groups_groups, the nesting between groups that are not deleted
+--
+create view active_groups_groups as
+select * from groups_groups
+where group_id in (select id from groups where deleted_at is null)
+and member_id in (select id from groups where deleted_at is null);
+-- names of deleted users and groups can be taken again
+alter table users drop constraint users_org_username_key;
+alter table users drop constraint users_org_email_key;
+create unique index users_org_username_key on users (org_id, username) where deleted_at is null;
+create unique index users_org_email_key on users (org_id, email) where deleted_at is null;
+alter table groups drop constraint groups_org_name_key;
+create unique index groups_org_name_key on groups (org_id, name) where deleted_at is null;
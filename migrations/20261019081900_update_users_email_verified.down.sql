@@ -0,0 +1 @@
+-- users verified before email verifications existed can not be told apart
@@ -0,0 +1 @@
+update users set email_verified_at = created_at where email_verified_at is null;
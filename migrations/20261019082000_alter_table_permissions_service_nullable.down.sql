@@ -0,0 +1,2 @@
+update permissions set service = 'gaia' where service is null;
+alter table permissions alter column service set not null;
@@ -0,0 +1,6 @@
+--
+-- permissions taken over from the groups belong to no service until one
+-- registers them, gaia registers its own again when it starts
+--
+alter table permissions alter column service drop not null;
+update permissions set service = null where service = 'gaia';
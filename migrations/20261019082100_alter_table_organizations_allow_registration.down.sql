@@ -0,0 +1 @@
+alter table organizations drop column allow_registration;
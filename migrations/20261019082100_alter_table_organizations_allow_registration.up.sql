@@ -0,0 +1,5 @@
+-- users can only register themselves into organizations that allow it, the
+-- system organization keeps allowing it as before
+alter table organizations add column allow_registration boolean not null default false;
+update organizations set allow_registration = true
+where id = '00000000-0000-0000-0000-000000000000';
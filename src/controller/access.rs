@@ -0,0 +1,100 @@
+//! Grants on a single user or group, shared by their controllers.
+
+use axum::{http::StatusCode, Json};
+use uuid::Uuid;
+
+use crate::{
+    model::{Grant, GrantDto},
+    repository::{GrantRepository, PermissionRepository},
+    security::{permission, scope, Jwt},
+};
+
+use super::Errors;
+
+/// Checks `permission` on the resource, from the token of the caller or a
+/// grant on the resource.
+pub async fn authorize(
+    jwt: &Jwt,
+    grants: &GrantRepository,
+    resource_type: &str,
+    resource_id: Uuid,
+    permission: &str,
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    if !scope::allows(jwt, grants, resource_type, resource_id, permission)
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Err(Errors::forbidden());
+    }
+    Ok(())
+}
+
+pub async fn list(
+    jwt: &Jwt,
+    grants: &GrantRepository,
+    resource_type: &str,
+    resource_id: Uuid,
+) -> Result<Json<Vec<Grant>>, (StatusCode, Json<Errors>)> {
+    let read = format!("{}:read", resource_type);
+    authorize(jwt, grants, resource_type, resource_id, &read).await?;
+
+    grants
+        .find_by_resource(resource_type, resource_id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+/// Gives a permission on the resource to a user or a group, the caller must
+/// be able to update the resource and have the permission on it.
+pub async fn grant(
+    jwt: &Jwt,
+    grants: &GrantRepository,
+    permissions: &PermissionRepository,
+    resource_type: &str,
+    resource_id: Uuid,
+    dto: GrantDto,
+) -> Result<Json<Grant>, (StatusCode, Json<Errors>)> {
+    let update = format!("{}:update", resource_type);
+    authorize(jwt, grants, resource_type, resource_id, &update).await?;
+
+    if dto.user_id.is_some() == dto.group_id.is_some() {
+        return Err(Errors::unprocessable(
+            "either user_id or group_id is required",
+        ));
+    }
+    let namespace = format!("{}:", resource_type);
+    let registry = permissions.names().await.map_err(Errors::sql)?;
+    if !dto.permission.starts_with(&namespace)
+        || !permission::is_registered(&registry, &dto.permission)
+    {
+        return Err(Errors::unprocessable(&format!(
+            "invalid permission: {}",
+            dto.permission
+        )));
+    }
+    authorize(jwt, grants, resource_type, resource_id, &dto.permission).await?;
+
+    grants
+        .create(jwt.org_id, resource_type, resource_id, dto)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+pub async fn revoke(
+    jwt: &Jwt,
+    grants: &GrantRepository,
+    resource_type: &str,
+    resource_id: Uuid,
+    id: Uuid,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    let update = format!("{}:update", resource_type);
+    authorize(jwt, grants, resource_type, resource_id, &update).await?;
+
+    grants
+        .delete(id, resource_type, resource_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
@@ -1,13 +1,29 @@
-use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use jsonwebtoken::jwk::JwkSet;
 use serde::Serialize;
 
 use crate::{
-    model::{LoginDto, UserWithGroups},
-    repository::UserRepository,
-    security::{self, password},
+    mail::Mailer,
+    model::{
+        Group, LoginCodeDto, LoginDto, LoginLinkDto, MfaLoginDto, PasswordlessDto,
+        PasswordlessMethod, UserWithGroups,
+    },
+    repository::{
+        LoginTokenRepository, MfaRepository, OrganizationRepository, SessionRepository,
+        UserRepository,
+    },
+    security::{self, account, mfa, password, passwordless, verification, ClientInfo, Jwt},
+    state::AppState,
 };
 
-use super::Errors;
+use super::{registration, Errors};
 
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
@@ -16,22 +32,70 @@ pub struct LoginResponse {
     pub token: String,
 }
 
-pub fn router(repo: UserRepository) -> Router {
-    Router::new().route("/login", post(login)).with_state(repo)
+#[derive(Debug, Serialize)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaEnrollment {
+    pub mfa_enrollment_required: bool,
+    pub enrollment_token: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResult {
+    Authenticated(Box<LoginResponse>),
+    MfaRequired(MfaChallenge),
+    MfaEnrollmentRequired(MfaEnrollment),
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/login", post(login))
+        .route("/login/mfa", post(login_mfa))
+        .route("/login/email", post(login_email))
+        .route("/login/link", post(login_link))
+        .route("/login/code", post(login_code))
+        .route("/refresh", post(refresh))
+        .route("/.well-known/jwks.json", get(jwks))
+}
+
+/// Publishes the public key verifying the access tokens, services verify the
+/// tokens themselves with it instead of calling this service on each request.
+#[axum::debug_handler(state = AppState)]
+pub async fn jwks() -> Result<Json<JwkSet>, (StatusCode, Json<Errors>)> {
+    security::jwt::key_set().map(Json)
 }
 
 /// Authenticates a user using a username and password.
 ///
+/// Users with MFA enabled receive a short lived `mfa_token` instead of an
+/// access token, which must be sent to `/login/mfa` with a recovery code.
+/// Members of a group that requires MFA without a factor enrolled receive an
+/// `enrollment_token` that is only accepted by `POST /profile/mfa`.
+///
 /// # Errors
 ///
 /// * `unauthorized` - if the username or password is incorrect
+/// * `forbidden` - if the account is locked, disabled or deleted, or the email
+///   address is not verified and the email policy blocks login
 /// * `internal_error` - if there was a problem with the database or password hashing
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn login(
     State(repo): State<UserRepository>,
+    State(organizations): State<OrganizationRepository>,
+    State(sessions): State<SessionRepository>,
+    client: ClientInfo,
     Json(dto): Json<LoginDto>,
-) -> Result<Json<LoginResponse>, (StatusCode, Json<Errors>)> {
-    let user = match repo.find_by_username(dto.username).await {
+) -> Result<Json<LoginResult>, (StatusCode, Json<Errors>)> {
+    let found = match organizations.resolve(dto.organization.as_deref()).await {
+        Ok(org_id) => repo.find_by_username(org_id, dto.username).await,
+        Err(err) => Err(err),
+    };
+    let user = match found {
         Ok(user) => user,
         Err(sqlx::Error::RowNotFound) => {
             return Err(Errors::unauthorized("username or password is incorrect"));
@@ -40,15 +104,269 @@ pub async fn login(
     };
 
     match password::check(&user.user.password_hash, &dto.password) {
-        Ok(checked) => {
-            if checked {
-                match security::jwt::generate_token(&user) {
-                    Ok(token) => return Ok(Json(LoginResponse { user, token })),
-                    Err(err) => return Err(err),
-                }
-            }
-            Err(Errors::unauthorized("username or password is incorrect"))
-        }
+        Ok(true) => complete_login(&sessions, &client, user).await.map(Json),
+        Ok(false) => Err(Errors::unauthorized("username or password is incorrect")),
         Err(err) => Err(Errors::argon2(err)),
     }
 }
+
+/// Sends a login link or a 6 digit code to the email address of a user whose
+/// groups allow passwordless login. Always returns `accepted` so it can not be
+/// used to find out which addresses are registered. No code is sent while the
+/// user has no failed code attempts left.
+///
+/// # Errors
+///
+/// * `internal_error` - if there was a problem with the database, password
+///   hashing or the mailer
+#[axum::debug_handler(state = AppState)]
+pub async fn login_email(
+    State(repo): State<UserRepository>,
+    State(organizations): State<OrganizationRepository>,
+    State(tokens): State<LoginTokenRepository>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Json(dto): Json<PasswordlessDto>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    let found = match organizations.resolve(dto.organization.as_deref()).await {
+        Ok(org_id) => repo.find_by_email(org_id, dto.email).await,
+        Err(err) => Err(err),
+    };
+    let user = match found {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => return Ok(StatusCode::ACCEPTED),
+        Err(err) => return Err(Errors::sql(err)),
+    };
+    if !Group::allows_passwordless(user.all_groups()) {
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    // a new code must not give more attempts at guessing one
+    if dto.method == PasswordlessMethod::Code
+        && tokens
+            .code_attempts_spent(user.user.id)
+            .await
+            .map_err(Errors::sql)?
+    {
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    let (secret, body) = match dto.method {
+        PasswordlessMethod::Link => {
+            let token = verification::token();
+            let body = format!(
+                "Use the link below to log in:\n\n{}\n",
+                passwordless::link(&token)
+            );
+            (token, body)
+        }
+        PasswordlessMethod::Code => {
+            let code = passwordless::code();
+            let body = format!("Your login code is {}\n", code);
+            (code, body)
+        }
+    };
+    let hash = password::hash(&secret).map_err(Errors::argon2)?;
+    tokens
+        .create(
+            user.user.id,
+            dto.method.as_str(),
+            hash,
+            passwordless::expires_at(),
+        )
+        .await
+        .map_err(Errors::sql)?;
+    mailer
+        .send(&user.user.email, "Log in", &body)
+        .await
+        .map_err(|err| Errors::internal(&err))?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Authenticates a user with the token of an emailed login link.
+///
+/// # Errors
+///
+/// * `unauthorized` - if the token is invalid, expired or was already used, or
+///   the groups of the user no longer allow passwordless login
+/// * `internal_error` - if there was a problem with the database or password hashing
+#[axum::debug_handler(state = AppState)]
+pub async fn login_link(
+    State(repo): State<UserRepository>,
+    State(tokens): State<LoginTokenRepository>,
+    State(sessions): State<SessionRepository>,
+    client: ClientInfo,
+    Json(dto): Json<LoginLinkDto>,
+) -> Result<Json<LoginResult>, (StatusCode, Json<Errors>)> {
+    let hash = password::hash(&dto.token).map_err(Errors::argon2)?;
+    let id = match tokens.consume_link(hash).await {
+        Ok(id) => id,
+        Err(sqlx::Error::RowNotFound) => return Err(Errors::unauthorized("invalid login link")),
+        Err(err) => return Err(Errors::sql(err)),
+    };
+
+    let user = repo.find_with_groups(id).await.map_err(Errors::sql)?;
+    if !Group::allows_passwordless(user.all_groups()) {
+        return Err(Errors::unauthorized("invalid login link"));
+    }
+    complete_login(&sessions, &client, user).await.map(Json)
+}
+
+/// Authenticates a user with an emailed 6 digit code.
+///
+/// # Errors
+///
+/// * `unauthorized` - if the code is incorrect, expired or was already used, or
+///   the groups of the user no longer allow passwordless login
+/// * `internal_error` - if there was a problem with the database or password hashing
+#[axum::debug_handler(state = AppState)]
+pub async fn login_code(
+    State(repo): State<UserRepository>,
+    State(organizations): State<OrganizationRepository>,
+    State(tokens): State<LoginTokenRepository>,
+    State(sessions): State<SessionRepository>,
+    client: ClientInfo,
+    Json(dto): Json<LoginCodeDto>,
+) -> Result<Json<LoginResult>, (StatusCode, Json<Errors>)> {
+    let found = match organizations.resolve(dto.organization.as_deref()).await {
+        Ok(org_id) => repo.find_by_email(org_id, dto.email).await,
+        Err(err) => Err(err),
+    };
+    let user = match found {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => return Err(Errors::unauthorized("invalid login code")),
+        Err(err) => return Err(Errors::sql(err)),
+    };
+    if !Group::allows_passwordless(user.all_groups()) {
+        return Err(Errors::unauthorized("invalid login code"));
+    }
+
+    let hash = password::hash(dto.code.trim()).map_err(Errors::argon2)?;
+    if !tokens
+        .consume_code(user.user.id, hash)
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Err(Errors::unauthorized("invalid login code"));
+    }
+    complete_login(&sessions, &client, user).await.map(Json)
+}
+
+/// Issues the access token of a user whose first factor was checked, or the
+/// MFA challenge or enrollment token if a second factor is needed.
+async fn complete_login(
+    sessions: &SessionRepository,
+    client: &ClientInfo,
+    user: UserWithGroups,
+) -> Result<LoginResult, (StatusCode, Json<Errors>)> {
+    account::check(&user.user)?;
+    if user.user.email_verified_at.is_none()
+        && (verification::policy().blocks_login()
+            || user.user.registered_at.is_some() && registration::requires_email_verification())
+    {
+        return Err(Errors::email_not_verified());
+    }
+    if user.user.mfa_enabled_at.is_some() {
+        let mfa_token =
+            security::jwt::generate_scoped_token(user.user.id, mfa::MFA_SCOPE, mfa::MFA_TOKEN_TTL)?;
+        return Ok(LoginResult::MfaRequired(MfaChallenge {
+            mfa_required: true,
+            mfa_token,
+        }));
+    }
+    if Group::requires_mfa(user.all_groups()) {
+        let enrollment_token = security::jwt::generate_scoped_token(
+            user.user.id,
+            mfa::MFA_ENROLLMENT_SCOPE,
+            mfa::MFA_ENROLLMENT_TOKEN_TTL,
+        )?;
+        return Ok(LoginResult::MfaEnrollmentRequired(MfaEnrollment {
+            mfa_enrollment_required: true,
+            enrollment_token,
+        }));
+    }
+    let response = start_session(sessions, client, user).await?;
+    Ok(LoginResult::Authenticated(Box::new(response)))
+}
+
+/// Records a new session for the user and issues its access token.
+async fn start_session(
+    sessions: &SessionRepository,
+    client: &ClientInfo,
+    user: UserWithGroups,
+) -> Result<LoginResponse, (StatusCode, Json<Errors>)> {
+    let policy = Group::session_policy(user.all_groups());
+    let session = sessions
+        .create(user.user.id, client, &policy)
+        .await
+        .map_err(Errors::sql)?;
+    let token = security::jwt::generate_token(&user, session.id)?;
+    Ok(LoginResponse { user, token })
+}
+
+/// Completes the login of a user with MFA enabled using a recovery code.
+///
+/// # Errors
+///
+/// * `unauthorized` - if the mfa token is invalid or expired, or the code is
+///   incorrect or was already used
+/// * `internal_error` - if there was a problem with the database or password hashing
+#[axum::debug_handler(state = AppState)]
+pub async fn login_mfa(
+    State(repo): State<UserRepository>,
+    State(mfa_repo): State<MfaRepository>,
+    State(sessions): State<SessionRepository>,
+    client: ClientInfo,
+    Json(dto): Json<MfaLoginDto>,
+) -> Result<Json<LoginResponse>, (StatusCode, Json<Errors>)> {
+    let id = security::jwt::verify_scoped_token(&dto.mfa_token, mfa::MFA_SCOPE)?;
+    let hash = mfa::hash_recovery_code(&dto.code).map_err(Errors::argon2)?;
+
+    if !mfa_repo
+        .use_recovery_code(id, hash)
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Err(Errors::unauthorized("invalid recovery code"));
+    }
+
+    let user = repo.find_with_groups(id).await.map_err(Errors::sql)?;
+    account::check(&user.user)?;
+    start_session(&sessions, &client, user).await.map(Json)
+}
+
+/// Issues a new token for the current user with its current groups, for the
+/// same session. The session policy of the groups is applied again, which may
+/// revoke older sessions.
+///
+/// # Errors
+///
+/// * `unauthorized` - if the token is invalid, expired or its session was revoked
+/// * `forbidden` - if the account is locked, disabled or deleted
+/// * `internal_error` - if there was a problem with the database
+#[axum::debug_handler(state = AppState)]
+pub async fn refresh(
+    State(repo): State<UserRepository>,
+    State(sessions): State<SessionRepository>,
+    client: ClientInfo,
+    jwt: Jwt,
+) -> Result<Json<LoginResponse>, (StatusCode, Json<Errors>)> {
+    let user = match repo.find_with_groups(jwt.id).await {
+        Ok(user) => user,
+        Err(sqlx::Error::RowNotFound) => return Err(Errors::unauthorized("invalid token")),
+        Err(err) => return Err(Errors::sql(err)),
+    };
+    account::check(&user.user)?;
+    match jwt.session_id {
+        Some(session_id) => {
+            let policy = Group::session_policy(user.all_groups());
+            sessions
+                .apply_policy(session_id, user.user.id, &policy)
+                .await
+                .map_err(Errors::sql)?;
+            let token = security::jwt::generate_token(&user, session_id)?;
+            Ok(Json(LoginResponse { user, token }))
+        }
+        // tokens issued before sessions were recorded
+        None => start_session(&sessions, &client, user).await.map(Json),
+    }
+}
@@ -0,0 +1,208 @@
+//! Authorization decisions for other services, with the same matching as the
+//! checks of this service.
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    model::{
+        AuthorizationDecision, AuthorizationDecisions, AuthorizeBatchDto, AuthorizeDto, CheckDto,
+        UserWithGroups,
+    },
+    security::{
+        account, jwt, permission,
+        policy::{self, Effect},
+        scope, ClientInfo, Jwt,
+    },
+    state::AppState,
+};
+
+use super::Errors;
+
+/// Most checks a single request may ask for.
+const MAX_CHECKS: usize = 100;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", post(authorize))
+        .route("/batch", post(authorize_batch))
+}
+
+/// Checks a permission for the holder of a token, or for any user of the
+/// organization of the caller if it has `authorization:check`. Invalid
+/// tokens and inactive accounts are denied with the reason.
+///
+/// # Errors
+///
+/// * `unauthorized` - if a subject is given without authenticating
+/// * `forbidden` - if the caller may not check the permissions of others
+/// * `not_found` - if the subject is not a user of the organization
+/// * `unprocessable_entity` - if neither or both a token and a subject are given
+#[axum::debug_handler(state = AppState)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    caller: Option<Jwt>,
+    client: ClientInfo,
+    Json(dto): Json<AuthorizeDto>,
+) -> Result<Json<AuthorizationDecision>, (StatusCode, Json<Errors>)> {
+    let subject = resolve(&state, caller, dto.token, dto.subject).await?;
+    let mut decisions = decide(&state, &client, subject, vec![dto.check]).await?;
+    Ok(Json(decisions.decisions.remove(0)))
+}
+
+/// Same as `authorize` for up to 100 permissions at once.
+///
+/// # Errors
+///
+/// * `unprocessable_entity` - if more than 100 checks are given, or as for
+///   `authorize`
+#[axum::debug_handler(state = AppState)]
+pub async fn authorize_batch(
+    State(state): State<AppState>,
+    caller: Option<Jwt>,
+    client: ClientInfo,
+    Json(dto): Json<AuthorizeBatchDto>,
+) -> Result<Json<AuthorizationDecisions>, (StatusCode, Json<Errors>)> {
+    let subject = resolve(&state, caller, dto.token, dto.subject).await?;
+    decide(&state, &client, subject, dto.checks).await.map(Json)
+}
+
+/// The subject whose permissions are checked, or why everything is denied.
+pub(super) enum Subject {
+    Found(Jwt, Option<Box<UserWithGroups>>),
+    Refused(Option<Uuid>, String),
+}
+
+async fn resolve(
+    state: &AppState,
+    caller: Option<Jwt>,
+    token: Option<String>,
+    subject: Option<Uuid>,
+) -> Result<Subject, (StatusCode, Json<Errors>)> {
+    match (token, subject) {
+        (Some(token), None) => match jwt::authenticate(&token, state).await {
+            Ok(jwt) => Ok(Subject::Found(jwt, None)),
+            Err((StatusCode::UNAUTHORIZED, Json(err))) => Ok(Subject::Refused(None, err.error)),
+            Err(err) => Err(err),
+        },
+        (None, Some(id)) => {
+            let Some(caller) = caller else {
+                return Err(Errors::unauthorized("authentication is required"));
+            };
+            if !caller.has_permission("authorization:check") {
+                return Err(Errors::forbidden());
+            }
+            let user = state
+                .users
+                .find_with_groups(id)
+                .await
+                .map_err(Errors::sql)?;
+            if user.user.org_id != caller.org_id {
+                return Err(Errors::not_found());
+            }
+            if let Err((_, Json(err))) = account::check(&user.user) {
+                return Ok(Subject::Refused(Some(id), err.error));
+            }
+            Ok(Subject::Found(Jwt::for_user(&user), Some(Box::new(user))))
+        }
+        _ => Err(Errors::unprocessable("either token or subject is required")),
+    }
+}
+
+pub(super) async fn decide(
+    state: &AppState,
+    client: &ClientInfo,
+    subject: Subject,
+    checks: Vec<CheckDto>,
+) -> Result<AuthorizationDecisions, (StatusCode, Json<Errors>)> {
+    if checks.len() > MAX_CHECKS {
+        return Err(Errors::unprocessable(&format!(
+            "at most {} checks are allowed",
+            MAX_CHECKS
+        )));
+    }
+    let (jwt, mut user) = match subject {
+        Subject::Found(jwt, user) => (jwt, user),
+        Subject::Refused(id, reason) => {
+            let decisions = checks
+                .into_iter()
+                .map(|check| AuthorizationDecision {
+                    permission: check.permission,
+                    allowed: false,
+                    reason: reason.clone(),
+                })
+                .collect();
+            return Ok(AuthorizationDecisions {
+                subject: id,
+                decisions,
+            });
+        }
+    };
+
+    let mut decisions = Vec::new();
+    for check in checks {
+        let (mut allowed, mut reason) = match &check.resource {
+            Some(resource) => scope::check(
+                &jwt,
+                &state.grants,
+                &resource.resource_type,
+                resource.id,
+                &check.permission,
+            )
+            .await
+            .map_err(Errors::sql)?,
+            None => {
+                let reason = permission::check(&jwt.perms, &check.permission);
+                (reason.allowed(), reason.to_string())
+            }
+        };
+        if state.policies.applies_to(&check.permission) {
+            if user.is_none() {
+                let found = state
+                    .users
+                    .find_with_groups(jwt.id)
+                    .await
+                    .map_err(Errors::sql)?;
+                user = Some(Box::new(found));
+            }
+            let attributes = policy::attributes(
+                policy::subject(user.as_deref().unwrap(), &jwt.perms),
+                resource(&check),
+                state.policies.context(client),
+            );
+            let rule = state
+                .policies
+                .deciding_rule(&check.permission, &jwt.perms, &attributes);
+            if let Some(rule) = rule {
+                allowed = rule.effect == Effect::Allow;
+                reason = match rule.effect {
+                    Effect::Allow => format!("allowed by policy {}", rule.name),
+                    Effect::Deny => format!("denied by policy {}", rule.name),
+                };
+            }
+        }
+        decisions.push(AuthorizationDecision {
+            permission: check.permission,
+            allowed,
+            reason,
+        });
+    }
+    Ok(AuthorizationDecisions {
+        subject: Some(jwt.id),
+        decisions,
+    })
+}
+
+/// Attributes of the resource given to the policies, its type, id and the
+/// attributes sent by the service.
+fn resource(check: &CheckDto) -> Value {
+    match &check.resource {
+        Some(resource) => json!({
+            "type": resource.resource_type,
+            "id": resource.id,
+            "attributes": resource.attributes,
+        }),
+        None => json!({}),
+    }
+}
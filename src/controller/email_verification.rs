@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use uuid::Uuid;
+
+use crate::{
+    mail::Mailer,
+    model::VerifyEmailDto,
+    repository::EmailVerificationRepository,
+    security::{password, verification},
+    state::AppState,
+};
+
+use super::Errors;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", get(confirm_link).post(confirm))
+}
+
+/// Sends a verification link to `email`, the address is only marked as
+/// verified on the user once the link is used.
+pub async fn send(
+    repo: &EmailVerificationRepository,
+    mailer: &dyn Mailer,
+    user_id: Uuid,
+    email: &str,
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    let token = verification::token();
+    let hash = password::hash(&token).map_err(Errors::argon2)?;
+    repo.create(user_id, email, hash, verification::expires_at())
+        .await
+        .map_err(Errors::sql)?;
+
+    let body = format!(
+        "Use the link below to verify your email address:\n\n{}\n",
+        verification::link(&token)
+    );
+    mailer
+        .send(email, "Verify your email address", &body)
+        .await
+        .map_err(|err| Errors::internal(&err))
+}
+
+/// Verifies an email address using the token of a verification link.
+///
+/// # Errors
+///
+/// * `not_found` - if the token does not exist, is expired or was already used
+/// * `internal_error` - if there was a problem with the database or password hashing
+#[axum::debug_handler(state = AppState)]
+pub async fn confirm(
+    State(repo): State<EmailVerificationRepository>,
+    Json(dto): Json<VerifyEmailDto>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    let hash = password::hash(&dto.token).map_err(Errors::argon2)?;
+    repo.confirm(hash).await.map_err(Errors::sql)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn confirm_link(
+    State(repo): State<EmailVerificationRepository>,
+    Query(dto): Query<VerifyEmailDto>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    confirm(State(repo), Json(dto)).await
+}
+
+/// Used by handlers that can not fail because of the mailer, like user
+/// creation, the user can ask for a new link later.
+pub async fn send_or_log(
+    repo: &EmailVerificationRepository,
+    mailer: &Arc<dyn Mailer>,
+    user_id: Uuid,
+    email: &str,
+) {
+    if let Err((_, Json(err))) = send(repo, mailer.as_ref(), user_id, email).await {
+        eprintln!(
+            "failed to send email verification to {}: {}",
+            email, err.error
+        );
+    }
+}
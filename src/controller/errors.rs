@@ -2,7 +2,9 @@ use axum::{http::StatusCode, Json};
 
 #[derive(serde::Serialize)]
 pub struct Errors {
-    error: String,
+    pub error: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
 }
 
 impl Errors {
@@ -11,6 +13,7 @@ impl Errors {
             StatusCode::UNAUTHORIZED,
             Json(Errors {
                 error: String::from(err),
+                code: None,
             }),
         )
     }
@@ -20,6 +23,7 @@ impl Errors {
             StatusCode::NOT_FOUND,
             Json(Errors {
                 error: String::from("object not found"),
+                code: None,
             }),
         )
     }
@@ -29,6 +33,77 @@ impl Errors {
             StatusCode::FORBIDDEN,
             Json(Errors {
                 error: String::from("you have not permission for acess this content"),
+                code: None,
+            }),
+        )
+    }
+
+    pub fn unprocessable(err: &str) -> (StatusCode, Json<Errors>) {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(Errors {
+                error: String::from(err),
+                code: None,
+            }),
+        )
+    }
+
+    pub fn email_not_verified() -> (StatusCode, Json<Errors>) {
+        (
+            StatusCode::FORBIDDEN,
+            Json(Errors {
+                error: String::from("email address is not verified"),
+                code: Some("email_not_verified"),
+            }),
+        )
+    }
+
+    pub fn account_locked() -> (StatusCode, Json<Errors>) {
+        (
+            StatusCode::FORBIDDEN,
+            Json(Errors {
+                error: String::from("account is locked"),
+                code: Some("account_locked"),
+            }),
+        )
+    }
+
+    pub fn account_disabled() -> (StatusCode, Json<Errors>) {
+        (
+            StatusCode::FORBIDDEN,
+            Json(Errors {
+                error: String::from("account is disabled"),
+                code: Some("account_disabled"),
+            }),
+        )
+    }
+
+    pub fn account_deleted() -> (StatusCode, Json<Errors>) {
+        (
+            StatusCode::FORBIDDEN,
+            Json(Errors {
+                error: String::from("account was deleted"),
+                code: Some("account_deleted"),
+            }),
+        )
+    }
+
+    pub fn conflict(err: &str) -> (StatusCode, Json<Errors>) {
+        (
+            StatusCode::CONFLICT,
+            Json(Errors {
+                error: String::from(err),
+                code: None,
+            }),
+        )
+    }
+
+    pub fn last_root() -> (StatusCode, Json<Errors>) {
+        (
+            StatusCode::CONFLICT,
+            Json(Errors {
+                error: String::from("the operation would leave no active root user"),
+                code: Some("last_root"),
             }),
         )
     }
@@ -38,6 +113,7 @@ impl Errors {
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(Errors {
                 error: String::from(err),
+                code: None,
             }),
         )
     }
@@ -0,0 +1,158 @@
+//! Forward authentication for reverse proxies such as Traefik or nginx, they
+//! ask whether a request may pass before forwarding it.
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use axum_extra::headers::{authorization::Bearer, Authorization, Cookie, HeaderMapExt};
+
+use crate::{
+    model::CheckDto,
+    security::{forward_auth, jwt, ClientInfo, Jwt},
+    state::AppState,
+};
+
+use super::{
+    authorization::{self, Subject},
+    Errors,
+};
+
+/// Header telling the permission required by requests no rule applies to,
+/// only read when `FORWARD_AUTH_PERMISSION_HEADER` is `true` because clients
+/// could set it themselves unless the proxy overwrites it.
+const PERMISSION_HEADER: &str = "x-required-permission";
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", get(verify))
+}
+
+/// Authenticates the request forwarded by the proxy with a bearer token or
+/// the cookie named by `FORWARD_AUTH_COOKIE` (`gaia_token` by default), and
+/// checks the permission the route rules require, or the one in
+/// `X-Required-Permission` when the proxy is trusted to set it. Other requests
+/// only need to be authenticated.
+///
+/// Allowed requests get the identity of the user in `X-User-Id`,
+/// `X-User-Organization` and `X-User-Permissions`.
+///
+/// # Errors
+///
+/// * `unauthorized` - if the request is not authenticated, browsers are
+///   redirected to `FORWARD_AUTH_LOGIN_URL` instead when it is set
+/// * `forbidden` - if the user does not have the required permission
+#[axum::debug_handler(state = AppState)]
+pub async fn verify(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<Errors>)> {
+    let Some(token) = token(&headers) else {
+        return Ok(unauthenticated(&headers, "authentication is required"));
+    };
+    let jwt = match jwt::authenticate(&token, &state).await {
+        Ok(jwt) => jwt,
+        Err((StatusCode::UNAUTHORIZED, Json(err))) => {
+            return Ok(unauthenticated(&headers, &err.error));
+        }
+        Err(err) => return Err(err),
+    };
+
+    let uri = header_value(&headers, "x-forwarded-uri")
+        .or_else(|| header_value(&headers, "x-original-uri"))
+        .unwrap_or("/");
+    let method = header_value(&headers, "x-forwarded-method")
+        .or_else(|| header_value(&headers, "x-original-method"));
+    let permission =
+        forward_auth::required_permission(header_value(&headers, "x-forwarded-host"), method, uri)
+            .map(String::from)
+            .or_else(|| {
+                trusts_permission_header()
+                    .then(|| header_value(&headers, PERMISSION_HEADER).map(String::from))
+                    .flatten()
+            });
+    let identity = identity(&jwt);
+    if let Some(permission) = permission {
+        let check = CheckDto {
+            permission,
+            resource: None,
+        };
+        let decisions =
+            authorization::decide(&state, &client, Subject::Found(jwt, None), vec![check]).await?;
+        if !decisions.decisions.iter().all(|decision| decision.allowed) {
+            return Err(Errors::forbidden());
+        }
+    }
+    Ok((StatusCode::OK, identity).into_response())
+}
+
+fn trusts_permission_header() -> bool {
+    std::env::var("FORWARD_AUTH_PERMISSION_HEADER").is_ok_and(|value| value == "true")
+}
+
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn token(headers: &HeaderMap) -> Option<String> {
+    if let Some(Authorization(bearer)) = headers.typed_get::<Authorization<Bearer>>() {
+        return Some(bearer.token().to_string());
+    }
+    let name = std::env::var("FORWARD_AUTH_COOKIE").unwrap_or(String::from("gaia_token"));
+    headers
+        .typed_get::<Cookie>()
+        .and_then(|cookie| cookie.get(&name).map(String::from))
+}
+
+fn identity(jwt: &Jwt) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let values = [
+        ("x-user-id", jwt.id.to_string()),
+        ("x-user-organization", jwt.org_id.to_string()),
+        ("x-user-permissions", jwt.perms.join(",")),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+    headers
+}
+
+/// Redirects browsers to the login page with the address they asked for,
+/// other clients get `unauthorized`.
+fn unauthenticated(headers: &HeaderMap, reason: &str) -> Response {
+    let browser = header_value(headers, header::ACCEPT.as_str())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let login = std::env::var("FORWARD_AUTH_LOGIN_URL").ok();
+    match login {
+        Some(login) if browser => {
+            let mut location = login;
+            if let Some(host) = header_value(headers, "x-forwarded-host") {
+                let proto = header_value(headers, "x-forwarded-proto").unwrap_or("https");
+                let uri = header_value(headers, "x-forwarded-uri").unwrap_or("/");
+                let original = format!("{}://{}{}", proto, host, uri);
+                let separator = if location.contains('?') { '&' } else { '?' };
+                location = format!("{}{}redirect={}", location, separator, encode(&original));
+            }
+            (StatusCode::FOUND, [(header::LOCATION, location)]).into_response()
+        }
+        _ => Errors::unauthorized(reason).into_response(),
+    }
+}
+
+/// Percent-encodes a query parameter value.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -1,63 +1,679 @@
 use crate::{
-    model::{Group, GroupDto},
-    repository::GroupRepository,
+    model::{
+        Grant, GrantDto, Group, GroupDto, ListQuery, MemberDto, MembersPatchDto, MembershipRequest,
+        MembershipRequestDto, User, GROUP_RESOURCE, REQUEST_PENDING,
+    },
+    repository::{
+        GrantRepository, GroupRepository, MembershipRequestRepository, PermissionRepository,
+        UserRepository,
+    },
+    security::{permission, Jwt},
+    state::AppState,
 };
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
 use uuid::Uuid;
 
-pub fn routes(repo: GroupRepository) -> Router {
+use super::{access, membership, Errors};
+
+pub fn routes() -> Router<AppState> {
     Router::new()
-        .route("/", axum::routing::get(index))
-        .route("/:id", axum::routing::get(show))
-        .route("/", axum::routing::post(create))
-        .route("/:id", axum::routing::put(update))
-        .with_state(repo)
+        .route("/", get(index))
+        .route("/:id", get(show))
+        .route("/", post(create))
+        .route("/:id", put(update))
+        .route("/:id", delete(destroy))
+        .route("/:id/restore", post(restore))
+        .route("/:id/groups", get(member_groups))
+        .route("/:id/groups/:member_id", put(add_member_group))
+        .route("/:id/groups/:member_id", delete(remove_member_group))
+        .route("/:id/members", get(members))
+        .route("/:id/members", post(add_member))
+        .route("/:id/members", patch(update_members))
+        .route("/:id/members/:user_id", delete(remove_member))
+        .route("/:id/requests", get(requests))
+        .route("/:id/requests", post(request_membership))
+        .route("/:id/requests/:request_id/approve", post(approve_request))
+        .route("/:id/requests/:request_id/reject", post(reject_request))
+        .route("/:id/owners", get(owners))
+        .route("/:id/owners/:user_id", put(add_owner))
+        .route("/:id/owners/:user_id", delete(remove_owner))
+        .route("/:id/access", get(access))
+        .route("/:id/access", post(grant))
+        .route("/:id/access/:grant_id", delete(revoke_grant))
 }
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn index(
+    jwt: Jwt,
     State(repo): State<GroupRepository>,
-) -> Result<Json<Vec<Group>>, (StatusCode, String)> {
-    match repo.find_all().await {
-        Ok(groups) => Ok(Json(groups)),
-        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<Group>>, (StatusCode, Json<Errors>)> {
+    if !jwt.has_permission("group:read") || (query.include_deleted && !jwt.is_admin()) {
+        return Err(Errors::forbidden());
     }
+
+    repo.find_all(jwt.org_id, query.include_deleted)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
 }
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn show(
+    jwt: Jwt,
     State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
     Path(id): Path<Uuid>,
-) -> Result<Json<Group>, (StatusCode, String)> {
-    match repo.find_by_id(id).await {
-        Ok(group) => Ok(Json(group)),
-        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
-    }
+) -> Result<Json<Group>, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:read").await?;
+
+    repo.find_by_id(jwt.org_id, id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
 }
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn create(
+    jwt: Jwt,
     State(repo): State<GroupRepository>,
+    State(permissions): State<PermissionRepository>,
     Json(dto): Json<GroupDto>,
-) -> Result<Json<Group>, (StatusCode, String)> {
-    match repo.create(dto).await {
-        Ok(group) => Ok(Json(group)),
-        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+) -> Result<Json<Group>, (StatusCode, Json<Errors>)> {
+    if !jwt.has_permission("group:create") {
+        return Err(Errors::forbidden());
     }
+    validate_permissions(&permissions, &dto.permissions).await?;
+    validate_sessions(&dto)?;
+    check_grants(&jwt, &dto.permissions)?;
+
+    let (visible, editable) = if jwt.is_root() {
+        (dto.visible, dto.editable)
+    } else {
+        (Some(true), Some(true))
+    };
+    let locked = if jwt.is_admin() { dto.locked } else { None };
+
+    let dto = GroupDto {
+        visible,
+        editable,
+        locked,
+        ..dto
+    };
+    repo.create(jwt.org_id, dto)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
 }
 
-#[axum::debug_handler]
+/// Updates a group, non-editable groups can only be updated by root and the
+/// name and permissions of locked groups can not change until unlocked. Only
+/// admins can change whether the group requires MFA, and omitted settings
+/// keep their value.
+#[axum::debug_handler(state = AppState)]
 pub async fn update(
+    jwt: Jwt,
     State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    State(permissions): State<PermissionRepository>,
     Path(id): Path<Uuid>,
     Json(dto): Json<GroupDto>,
-) -> Result<Json<Group>, (StatusCode, String)> {
-    match repo.update(id, dto).await {
-        Ok(group) => Ok(Json(group)),
-        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+) -> Result<Json<Group>, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:update").await?;
+    validate_permissions(&permissions, &dto.permissions).await?;
+    validate_sessions(&dto)?;
+
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+    if group.locked && (dto.name != group.name || dto.permissions != group.permissions.0) {
+        return Err(Errors::conflict("group is locked"));
+    }
+    // permissions the group already had are kept as they are, and removing a
+    // deny entry grants what it denied
+    let added: Vec<String> = dto
+        .permissions
+        .iter()
+        .filter(|p| !group.permissions.contains(p))
+        .cloned()
+        .chain(
+            group
+                .permissions
+                .iter()
+                .filter(|p| !dto.permissions.contains(p))
+                .filter_map(|p| p.strip_prefix(permission::DENY_PREFIX))
+                .map(String::from),
+        )
+        .collect();
+    check_grants(&jwt, &added)?;
+
+    let (visible, editable) = if jwt.is_root() {
+        (
+            dto.visible.or(Some(group.visible)),
+            dto.editable.or(Some(group.editable)),
+        )
+    } else {
+        (Some(group.visible), Some(group.editable))
+    };
+    let (locked, require_mfa) = if jwt.is_admin() {
+        (
+            dto.locked.or(Some(group.locked)),
+            dto.require_mfa.or(Some(group.require_mfa)),
+        )
+    } else {
+        (Some(group.locked), Some(group.require_mfa))
+    };
+
+    // omitted settings keep their value
+    let dto = GroupDto {
+        visible,
+        editable,
+        locked,
+        require_mfa,
+        allow_passwordless: dto.allow_passwordless.or(Some(group.allow_passwordless)),
+        idle_timeout: dto.idle_timeout.or(group.idle_timeout),
+        max_sessions: dto.max_sessions.or(group.max_sessions),
+        ..dto
+    };
+    repo.update(jwt.org_id, id, dto)
+        .await
+        .map_err(Errors::sql)?
+        .map(Json)
+        .ok_or_else(Errors::last_root)
+}
+
+/// Soft-deletes a group, locked and non-editable groups can not be deleted.
+/// Its members lose its permissions until it is restored.
+#[axum::debug_handler(state = AppState)]
+pub async fn destroy(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:delete").await?;
+
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+    if group.locked {
+        return Err(Errors::conflict("group is locked"));
+    }
+
+    let deleted = repo.delete(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !deleted {
+        return Err(Errors::last_root());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Restores a deleted group with its members, owners and grants. The members
+/// get its permissions back, so like `update` only root can restore
+/// non-editable groups and the caller must be able to grant the permissions
+/// of the group and of the groups containing it.
+///
+/// # Errors
+///
+/// * `not_found` - if the group is not deleted
+/// * `conflict` - if another group took its name meanwhile
+#[axum::debug_handler(state = AppState)]
+pub async fn restore(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Group>, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:delete").await?;
+
+    let group = repo
+        .find_deleted(jwt.org_id, id)
+        .await
+        .map_err(Errors::sql)?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+    let parents = repo.parent_ids(id).await.map_err(Errors::sql)?;
+    let ancestors = repo
+        .find_with_ancestors(&parents)
+        .await
+        .map_err(Errors::sql)?;
+    let mut permissions = group.permissions();
+    permissions.extend(ancestors.iter().flat_map(Group::permissions));
+    check_grants(&jwt, &permissions)?;
+
+    repo.restore(jwt.org_id, id)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::Database(err) if err.is_unique_violation() => {
+                Errors::conflict("group name is taken")
+            }
+            err => Errors::sql(err),
+        })?;
+    repo.find_by_id(jwt.org_id, id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn member_groups(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Group>>, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:read").await?;
+
+    repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    repo.member_groups(id).await.map(Json).map_err(Errors::sql)
+}
+
+/// Makes the members of a group members of another one, they inherit the
+/// permissions of the containing group and of the groups containing it.
+#[axum::debug_handler(state = AppState)]
+pub async fn add_member_group(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:update").await?;
+
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+    repo.find_by_id(jwt.org_id, member_id)
+        .await
+        .map_err(Errors::sql)?;
+    let granted = repo.find_with_ancestors(&[id]).await.map_err(Errors::sql)?;
+    let permissions: Vec<String> = granted.iter().flat_map(Group::permissions).collect();
+    check_grants(&jwt, &permissions)?;
+
+    let added = repo
+        .add_member_group(id, member_id)
+        .await
+        .map_err(Errors::sql)?;
+    if !added {
+        return Err(Errors::unprocessable("groups can not contain each other"));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn remove_member_group(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, member_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:update").await?;
+
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+
+    let removed = repo
+        .remove_member_group(jwt.org_id, id, member_id)
+        .await
+        .map_err(Errors::sql)?;
+    if !removed {
+        return Err(Errors::last_root());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the direct members of a group, for its owners and the callers
+/// allowed to read it.
+#[axum::debug_handler(state = AppState)]
+pub async fn members(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<User>>, (StatusCode, Json<Errors>)> {
+    repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !repo.is_owner(id, jwt.id).await.map_err(Errors::sql)? {
+        access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:read").await?;
+    }
+
+    let mut members = repo.members(id).await.map_err(Errors::sql)?;
+    if !jwt.is_root() {
+        members.retain(|user| user.visible);
+    }
+    Ok(Json(members))
+}
+
+/// Adds a user to a group, only between `valid_from` and `valid_until` when
+/// they are given. An expired membership is replaced.
+///
+/// # Errors
+///
+/// * `conflict` - if the user is already a member beyond the requested time,
+///   the membership has to be removed to shorten it
+#[axum::debug_handler(state = AppState)]
+pub async fn add_member(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(users): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+    Json(dto): Json<MemberDto>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    membership::check_validity(dto.valid_from, dto.valid_until)?;
+    membership::check_add(&jwt, &repo, &users, &grants, id, &[dto.user_id]).await?;
+
+    let added = users
+        .add_to_group(dto.user_id, id, dto.valid_from, dto.valid_until)
+        .await
+        .map_err(Errors::sql)?;
+    if !added {
+        return Err(wider_membership());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn remove_member(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(users): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    membership::check_remove(&jwt, &repo, &users, &grants, id, &[user_id]).await?;
+
+    let removed = users
+        .remove_from_group(jwt.org_id, user_id, id)
+        .await
+        .map_err(Errors::sql)?;
+    if !removed {
+        return Err(Errors::last_root());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Adds and removes members of a group at once, nothing changes if one of
+/// the changes is refused.
+#[axum::debug_handler(state = AppState)]
+pub async fn update_members(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(users): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+    Json(dto): Json<MembersPatchDto>,
+) -> Result<Json<Vec<User>>, (StatusCode, Json<Errors>)> {
+    if dto.add.iter().any(|user_id| dto.remove.contains(user_id)) {
+        return Err(Errors::unprocessable(
+            "a user can not be both added and removed",
+        ));
+    }
+    if !dto.add.is_empty() {
+        membership::check_add(&jwt, &repo, &users, &grants, id, &dto.add).await?;
+    }
+    if !dto.remove.is_empty() {
+        membership::check_remove(&jwt, &repo, &users, &grants, id, &dto.remove).await?;
+    }
+
+    let updated = users
+        .update_members(jwt.org_id, id, &dto.add, &dto.remove)
+        .await
+        .map_err(Errors::sql)?;
+    if !updated {
+        return Err(Errors::last_root());
+    }
+    repo.members(id).await.map(Json).map_err(Errors::sql)
+}
+
+/// Lists the membership requests of a group to its owners.
+#[axum::debug_handler(state = AppState)]
+pub async fn requests(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    State(requests): State<MembershipRequestRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<MembershipRequest>>, (StatusCode, Json<Errors>)> {
+    membership::authorize(&jwt, &repo, &grants, id).await?;
+
+    requests
+        .find_by_group(id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+/// Asks for a temporary membership of the current user in a group.
+#[axum::debug_handler(state = AppState)]
+pub async fn request_membership(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(requests): State<MembershipRequestRepository>,
+    Path(id): Path<Uuid>,
+    Json(dto): Json<MembershipRequestDto>,
+) -> Result<(StatusCode, Json<MembershipRequest>), (StatusCode, Json<Errors>)> {
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !group.visible && !jwt.is_root() {
+        return Err(Errors::not_found());
+    }
+    membership::check_request_validity(dto.valid_from, dto.valid_until)?;
+    if requests
+        .has_pending(jwt.id, id)
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Err(Errors::conflict("a request is already pending"));
+    }
+
+    requests
+        .create(jwt.id, id, dto)
+        .await
+        .map(|request| (StatusCode::CREATED, Json(request)))
+        .map_err(Errors::sql)
+}
+
+/// Approves a membership request, with the same checks as adding the member.
+/// Users can not approve their own requests, and requests of users who are
+/// already members beyond the requested time are refused with `conflict`.
+#[axum::debug_handler(state = AppState)]
+pub async fn approve_request(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(users): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    State(requests): State<MembershipRequestRepository>,
+    Path((id, request_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<MembershipRequest>, (StatusCode, Json<Errors>)> {
+    let request = requests.find(request_id, id).await.map_err(Errors::sql)?;
+    membership::check_add(&jwt, &repo, &users, &grants, id, &[request.user_id]).await?;
+    check_pending(&jwt, &request)?;
+    membership::check_validity(request.valid_from, Some(request.valid_until))?;
+
+    requests
+        .approve(request_id, jwt.id)
+        .await
+        .map_err(Errors::sql)?
+        .map(Json)
+        .ok_or_else(wider_membership)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn reject_request(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    State(requests): State<MembershipRequestRepository>,
+    Path((id, request_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<MembershipRequest>, (StatusCode, Json<Errors>)> {
+    let request = requests.find(request_id, id).await.map_err(Errors::sql)?;
+    membership::authorize(&jwt, &repo, &grants, id).await?;
+    check_pending(&jwt, &request)?;
+
+    requests
+        .reject(request_id, jwt.id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+/// Refuses to shorten a membership, it has to be removed first.
+fn wider_membership() -> (StatusCode, Json<Errors>) {
+    Errors::conflict("the user is already a member beyond the requested time")
+}
+
+fn check_pending(jwt: &Jwt, request: &MembershipRequest) -> Result<(), (StatusCode, Json<Errors>)> {
+    if request.user_id == jwt.id {
+        return Err(Errors::forbidden());
+    }
+    if request.status != REQUEST_PENDING {
+        return Err(Errors::conflict("the request is already decided"));
+    }
+    Ok(())
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn owners(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<User>>, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:read").await?;
+
+    repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    repo.owners(id).await.map(Json).map_err(Errors::sql)
+}
+
+/// Makes a user owner of a group, owners can not appoint other owners. Owners
+/// manage the members, so the caller must be able to grant the permissions
+/// of the group, and only root can appoint itself.
+#[axum::debug_handler(state = AppState)]
+pub async fn add_owner(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(users): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:update").await?;
+
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !jwt.is_root() && (!group.editable || user_id == jwt.id) {
+        return Err(Errors::forbidden());
+    }
+    let granted = repo.find_with_ancestors(&[id]).await.map_err(Errors::sql)?;
+    let permissions: Vec<String> = granted.iter().flat_map(Group::permissions).collect();
+    check_grants(&jwt, &permissions)?;
+    membership::check_users(&jwt, &users, &[user_id]).await?;
+
+    repo.add_owner(id, user_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn remove_owner(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, GROUP_RESOURCE, id, "group:update").await?;
+
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+
+    repo.remove_owner(id, user_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+/// Lists who was given access to the group.
+#[axum::debug_handler(state = AppState)]
+pub async fn access(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Grant>>, (StatusCode, Json<Errors>)> {
+    repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    access::list(&jwt, &grants, GROUP_RESOURCE, id).await
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn grant(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    State(permissions): State<PermissionRepository>,
+    Path(id): Path<Uuid>,
+    Json(dto): Json<GrantDto>,
+) -> Result<Json<Grant>, (StatusCode, Json<Errors>)> {
+    let group = repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+    access::grant(&jwt, &grants, &permissions, GROUP_RESOURCE, id, dto).await
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn revoke_grant(
+    jwt: Jwt,
+    State(repo): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, grant_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    repo.find_by_id(jwt.org_id, id).await.map_err(Errors::sql)?;
+    access::revoke(&jwt, &grants, GROUP_RESOURCE, id, grant_id).await
+}
+
+/// Refuses permissions missing from the registry, mostly typos.
+async fn validate_permissions(
+    repo: &PermissionRepository,
+    permissions: &[String],
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    let registry = repo.names().await.map_err(Errors::sql)?;
+    match permissions
+        .iter()
+        .find(|p| !permission::is_registered(&registry, p))
+    {
+        Some(p) => Err(Errors::unprocessable(&format!("unknown permission: {}", p))),
+        None => Ok(()),
+    }
+}
+
+/// Refuses session settings that would end every session at once or forbid
+/// any session.
+fn validate_sessions(dto: &GroupDto) -> Result<(), (StatusCode, Json<Errors>)> {
+    if dto.idle_timeout.is_some_and(|minutes| minutes <= 0) {
+        return Err(Errors::unprocessable("idle_timeout must be positive"));
+    }
+    if dto.max_sessions.is_some_and(|max| max <= 0) {
+        return Err(Errors::unprocessable("max_sessions must be positive"));
+    }
+    Ok(())
+}
+
+/// Refuses permissions the caller could not grant.
+pub(super) fn check_grants(
+    jwt: &Jwt,
+    permissions: &[String],
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    if !permissions.iter().all(|p| jwt.can_grant(p)) {
+        return Err(Errors::forbidden());
     }
+    Ok(())
 }
@@ -0,0 +1,154 @@
+//! Checks on the direct members of a group, shared by the group and user
+//! endpoints changing them.
+
+use axum::{http::StatusCode, Json};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    model::{Group, GROUP_RESOURCE},
+    repository::{GrantRepository, GroupRepository, UserRepository},
+    security::Jwt,
+};
+
+use super::{access, group_controller::check_grants, Errors};
+
+/// Lets the owners of a group and the callers allowed to update it manage
+/// its members, returns true for owners.
+pub(super) async fn authorize(
+    jwt: &Jwt,
+    groups: &GroupRepository,
+    grants: &GrantRepository,
+    group_id: Uuid,
+) -> Result<bool, (StatusCode, Json<Errors>)> {
+    let group = groups
+        .find_by_id(jwt.org_id, group_id)
+        .await
+        .map_err(Errors::sql)?;
+    if groups
+        .is_owner(group_id, jwt.id)
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Ok(true);
+    }
+    access::authorize(jwt, grants, GROUP_RESOURCE, group_id, "group:update").await?;
+    if !group.editable && !jwt.is_root() {
+        return Err(Errors::forbidden());
+    }
+    Ok(false)
+}
+
+/// Hides users of other organizations and non-visible users from everyone
+/// but root, and only lets root change the groups of non-editable users.
+pub(super) async fn check_users(
+    jwt: &Jwt,
+    users: &UserRepository,
+    user_ids: &[Uuid],
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    for id in user_ids {
+        if !users
+            .in_organization(*id, jwt.org_id)
+            .await
+            .map_err(Errors::sql)?
+        {
+            return Err(Errors::not_found());
+        }
+        if jwt.is_root() {
+            continue;
+        }
+        if !users.is_visible(*id).await.map_err(Errors::sql)? {
+            return Err(Errors::not_found());
+        }
+        if !users.is_editable(*id).await.map_err(Errors::sql)? {
+            return Err(Errors::forbidden());
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the caller may add the users to the group. Owners may add
+/// anyone, other callers must also be able to grant the permissions of the
+/// group.
+pub async fn check_add(
+    jwt: &Jwt,
+    groups: &GroupRepository,
+    users: &UserRepository,
+    grants: &GrantRepository,
+    group_id: Uuid,
+    user_ids: &[Uuid],
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    let owner = authorize(jwt, groups, grants, group_id).await?;
+    check_users(jwt, users, user_ids).await?;
+    if !owner {
+        let granted = groups
+            .find_with_ancestors(&[group_id])
+            .await
+            .map_err(Errors::sql)?;
+        let permissions: Vec<String> = granted.iter().flat_map(Group::permissions).collect();
+        check_grants(jwt, &permissions)?;
+    }
+    Ok(())
+}
+
+/// Checks that the caller may remove the users from the group, the removal
+/// itself is refused if it leaves no active root user.
+pub async fn check_remove(
+    jwt: &Jwt,
+    groups: &GroupRepository,
+    users: &UserRepository,
+    grants: &GrantRepository,
+    group_id: Uuid,
+    user_ids: &[Uuid],
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    authorize(jwt, groups, grants, group_id).await?;
+    check_users(jwt, users, user_ids).await?;
+    Ok(())
+}
+
+/// Longest membership in minutes a user may request, set with
+/// `MEMBERSHIP_REQUEST_MAX_DURATION`, 8 hours by default.
+fn max_request_duration() -> i64 {
+    std::env::var("MEMBERSHIP_REQUEST_MAX_DURATION")
+        .map(|value| {
+            value
+                .parse()
+                .expect("MEMBERSHIP_REQUEST_MAX_DURATION must be a number")
+        })
+        .unwrap_or(8 * 60)
+}
+
+/// Refuses memberships ending before they start or already ended.
+pub fn check_validity(
+    valid_from: Option<i64>,
+    valid_until: Option<i64>,
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    let Some(valid_until) = valid_until else {
+        return Ok(());
+    };
+    if valid_until <= Utc::now().timestamp() {
+        return Err(Errors::unprocessable("valid_until must be in the future"));
+    }
+    if valid_from.is_some_and(|valid_from| valid_from >= valid_until) {
+        return Err(Errors::unprocessable(
+            "valid_until must be after valid_from",
+        ));
+    }
+    Ok(())
+}
+
+/// Same as `check_validity`, requested memberships must also not last longer
+/// than `MEMBERSHIP_REQUEST_MAX_DURATION`.
+pub fn check_request_validity(
+    valid_from: Option<i64>,
+    valid_until: i64,
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    check_validity(valid_from, Some(valid_until))?;
+    let start = valid_from.unwrap_or(0).max(Utc::now().timestamp());
+    if valid_until - start > max_request_duration() * 60 {
+        return Err(Errors::unprocessable(
+            "the requested membership is too long",
+        ));
+    }
+    Ok(())
+}
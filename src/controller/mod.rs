@@ -1,8 +1,39 @@
+mod access;
 mod errors;
+mod membership;
 
 pub mod auth_controller;
+pub mod authorization;
+pub mod email_verification;
+pub mod forward_auth;
 pub mod group_controller;
+pub mod organization_controller;
+pub mod permission_controller;
 pub mod profile;
+pub mod registration;
 pub mod user_controller;
 
 pub use errors::Errors;
+
+use axum::Router;
+
+use crate::state::AppState;
+
+/// Routes of every endpoint, `/register` only when registration is enabled.
+pub fn routes() -> Router<AppState> {
+    let app = Router::new()
+        .nest("/authorize", authorization::routes())
+        .nest("/forward-auth", forward_auth::routes())
+        .nest("/groups", group_controller::routes())
+        .nest("/users", user_controller::routes())
+        .nest("/organizations", organization_controller::routes())
+        .nest("/permissions", permission_controller::routes())
+        .nest("/profile", profile::routes())
+        .nest("/verify-email", email_verification::routes())
+        .nest("/", auth_controller::router());
+    if registration::enabled() {
+        app.nest("/register", registration::routes())
+    } else {
+        app
+    }
+}
@@ -0,0 +1,89 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use uuid::Uuid;
+
+use crate::{
+    model::{Organization, OrganizationDto},
+    repository::OrganizationRepository,
+    security::Jwt,
+    state::AppState,
+};
+
+use super::Errors;
+
+/// Organizations are managed by the super-admins, who then act in one of
+/// them by sending its id in the `X-Organization` header.
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index))
+        .route("/", post(create))
+        .route("/:id", get(show))
+        .route("/:id", put(update))
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn index(
+    jwt: Jwt,
+    State(repo): State<OrganizationRepository>,
+) -> Result<Json<Vec<Organization>>, (StatusCode, Json<Errors>)> {
+    if !jwt.is_super_admin() {
+        return Err(Errors::forbidden());
+    }
+
+    repo.find_all().await.map(Json).map_err(Errors::sql)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn show(
+    jwt: Jwt,
+    State(repo): State<OrganizationRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Organization>, (StatusCode, Json<Errors>)> {
+    if !jwt.is_super_admin() {
+        return Err(Errors::forbidden());
+    }
+
+    repo.find_by_id(id).await.map(Json).map_err(Errors::sql)
+}
+
+/// Creates an empty organization, its first groups and users are created by
+/// a super-admin acting in it.
+#[axum::debug_handler(state = AppState)]
+pub async fn create(
+    jwt: Jwt,
+    State(repo): State<OrganizationRepository>,
+    Json(dto): Json<OrganizationDto>,
+) -> Result<Json<Organization>, (StatusCode, Json<Errors>)> {
+    if !jwt.is_super_admin() {
+        return Err(Errors::forbidden());
+    }
+    validate(&dto)?;
+
+    repo.create(dto).await.map(Json).map_err(Errors::sql)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn update(
+    jwt: Jwt,
+    State(repo): State<OrganizationRepository>,
+    Path(id): Path<Uuid>,
+    Json(dto): Json<OrganizationDto>,
+) -> Result<Json<Organization>, (StatusCode, Json<Errors>)> {
+    if !jwt.is_super_admin() {
+        return Err(Errors::forbidden());
+    }
+    validate(&dto)?;
+
+    repo.update(id, dto).await.map(Json).map_err(Errors::sql)
+}
+
+fn validate(dto: &OrganizationDto) -> Result<(), (StatusCode, Json<Errors>)> {
+    if dto.name.trim().is_empty() {
+        return Err(Errors::unprocessable("name is required"));
+    }
+    Ok(())
+}
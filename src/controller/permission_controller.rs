@@ -0,0 +1,81 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::{
+    model::{Permission, RegisterPermissionsDto, SYSTEM_ORGANIZATION},
+    repository::PermissionRepository,
+    security::Jwt,
+    state::AppState,
+};
+
+use super::Errors;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(index))
+        .route("/", post(register))
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn index(
+    jwt: Jwt,
+    State(repo): State<PermissionRepository>,
+) -> Result<Json<Vec<Permission>>, (StatusCode, Json<Errors>)> {
+    if !jwt.has_permission("permission:read") {
+        return Err(Errors::forbidden());
+    }
+
+    repo.find_all().await.map(Json).map_err(Errors::sql)
+}
+
+/// Registers the permissions checked by a service, called by the services
+/// when they start. The registry is shared by every organization, only
+/// callers of the system organization can change it.
+///
+/// # Errors
+///
+/// * `forbidden` - if the caller can not register permissions
+/// * `unprocessable_entity` - if a name is empty or has a wildcard or deny prefix
+/// * `conflict` - if another service registered one of the permissions, the
+///   ones granted before any service registered them can be claimed
+#[axum::debug_handler(state = AppState)]
+pub async fn register(
+    jwt: Jwt,
+    State(repo): State<PermissionRepository>,
+    Json(dto): Json<RegisterPermissionsDto>,
+) -> Result<Json<Vec<Permission>>, (StatusCode, Json<Errors>)> {
+    if !jwt.has_permission("permission:register") || jwt.org_id != SYSTEM_ORGANIZATION {
+        return Err(Errors::forbidden());
+    }
+    if let Some(permission) = dto
+        .permissions
+        .iter()
+        .find(|p| p.name.is_empty() || p.name.contains(['*', '!']))
+    {
+        return Err(Errors::unprocessable(&format!(
+            "invalid permission name: {}",
+            permission.name
+        )));
+    }
+
+    let names: Vec<String> = dto.permissions.iter().map(|p| p.name.clone()).collect();
+    let taken = repo
+        .owned_by_others(&dto.service, &names)
+        .await
+        .map_err(Errors::sql)?;
+    if !taken.is_empty() {
+        return Err(Errors::conflict(&format!(
+            "permissions registered by another service: {}",
+            taken.join(", ")
+        )));
+    }
+
+    repo.register(&dto.service, dto.permissions)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
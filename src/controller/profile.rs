@@ -1,23 +1,52 @@
+use std::sync::Arc;
+
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::StatusCode,
-    routing::{get, put},
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use serde::Serialize;
+use uuid::Uuid;
 
 use crate::{
-    model::{ProfileDto, User, UserWithGroups},
-    repository::UserRepository,
-    security::Jwt,
+    mail::Mailer,
+    model::{EmailDto, MembershipRequest, ProfileDto, Session, User, UserWithGroups},
+    repository::{
+        EmailVerificationRepository, MembershipRequestRepository, MfaRepository, SessionRepository,
+        UserRepository,
+    },
+    security::{mfa, EnrollmentJwt, Jwt},
+    state::AppState,
 };
 
-use super::Errors;
+use super::{email_verification, Errors};
+
+#[derive(Debug, Serialize)]
+pub struct MfaStatus {
+    pub enabled: bool,
+    pub enabled_at: Option<i64>,
+    pub recovery_codes_remaining: i64,
+}
 
-pub fn routes(repo: UserRepository) -> Router {
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/", get(index))
         .route("/", put(update))
-        .with_state(repo)
+        .route("/email", put(update_email))
+        .route("/email/verification", post(resend_email_verification))
+        .route("/mfa", get(mfa_status))
+        .route("/mfa", post(mfa_enroll))
+        .route("/mfa/recovery-codes", post(mfa_recovery_codes))
+        .route("/sign-out", post(sign_out))
+        .route("/sessions", get(sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/requests", get(requests))
 }
 
 pub async fn index(
@@ -40,3 +69,159 @@ pub async fn update(
         .map(Json)
         .map_err(Errors::sql)
 }
+
+/// Signs the current user out everywhere, including the token of this request.
+pub async fn sign_out(
+    State(repo): State<UserRepository>,
+    jwt: Jwt,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    repo.bump_token_version(jwt.id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+/// Lists the active sessions of the current user.
+pub async fn sessions(
+    State(sessions): State<SessionRepository>,
+    jwt: Jwt,
+) -> Result<Json<Vec<Session>>, (StatusCode, Json<Errors>)> {
+    sessions
+        .find_active(jwt.id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+/// Lists the membership requests of the current user.
+pub async fn requests(
+    State(requests): State<MembershipRequestRepository>,
+    jwt: Jwt,
+) -> Result<Json<Vec<MembershipRequest>>, (StatusCode, Json<Errors>)> {
+    requests
+        .find_by_user(jwt.id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+/// Revokes a session of the current user, its tokens stop working.
+pub async fn revoke_session(
+    State(sessions): State<SessionRepository>,
+    jwt: Jwt,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    sessions
+        .revoke(id, jwt.id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+/// Requests an email change, the new address replaces the current one once
+/// the link sent to it is used.
+pub async fn update_email(
+    State(repo): State<UserRepository>,
+    State(verifications): State<EmailVerificationRepository>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    jwt: Jwt,
+    Json(body): Json<EmailDto>,
+) -> Result<Json<User>, (StatusCode, Json<Errors>)> {
+    let user = repo
+        .update_pending_email(jwt.id, body.email)
+        .await
+        .map_err(Errors::sql)?;
+    if let Some(email) = &user.pending_email {
+        email_verification::send(&verifications, mailer.as_ref(), user.id, email).await?;
+    }
+    Ok(Json(user))
+}
+
+/// Sends a new verification link to the pending email address, or to the
+/// current one if it is not verified yet.
+pub async fn resend_email_verification(
+    State(repo): State<UserRepository>,
+    State(verifications): State<EmailVerificationRepository>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    jwt: Jwt,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    let user = repo.find(jwt.id).await.map_err(Errors::sql)?;
+    let email = match (&user.pending_email, user.email_verified_at) {
+        (Some(email), _) => email,
+        (None, None) => &user.email,
+        (None, Some(_)) => return Err(Errors::conflict("email address is already verified")),
+    };
+    email_verification::send(&verifications, mailer.as_ref(), user.id, email).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn mfa_status(
+    State(repo): State<UserRepository>,
+    State(mfa_repo): State<MfaRepository>,
+    jwt: Jwt,
+) -> Result<Json<MfaStatus>, (StatusCode, Json<Errors>)> {
+    let user = repo.find(jwt.id).await.map_err(Errors::sql)?;
+    let remaining = mfa_repo
+        .remaining_recovery_codes(jwt.id)
+        .await
+        .map_err(Errors::sql)?;
+    Ok(Json(MfaStatus {
+        enabled: user.mfa_enabled_at.is_some(),
+        enabled_at: user.mfa_enabled_at,
+        recovery_codes_remaining: remaining,
+    }))
+}
+
+/// Enables MFA for the current user. The recovery codes are only shown in
+/// this response, they are stored hashed.
+///
+/// Also accepts the enrollment-only token returned by `login`, the user must
+/// log in again with a recovery code afterwards.
+pub async fn mfa_enroll(
+    State(repo): State<UserRepository>,
+    State(mfa_repo): State<MfaRepository>,
+    jwt: EnrollmentJwt,
+) -> Result<Json<RecoveryCodes>, (StatusCode, Json<Errors>)> {
+    let user = repo.find(jwt.id).await.map_err(Errors::sql)?;
+    if user.mfa_enabled_at.is_some() {
+        return Err(Errors::conflict("mfa is already enabled"));
+    }
+
+    let codes = replace_recovery_codes(&mfa_repo, &user).await?;
+    mfa_repo.enable(user.id).await.map_err(Errors::sql)?;
+    Ok(Json(codes))
+}
+
+/// Invalidates the unused recovery codes of the current user and generates
+/// a new set.
+pub async fn mfa_recovery_codes(
+    State(repo): State<UserRepository>,
+    State(mfa_repo): State<MfaRepository>,
+    jwt: Jwt,
+) -> Result<Json<RecoveryCodes>, (StatusCode, Json<Errors>)> {
+    let user = repo.find(jwt.id).await.map_err(Errors::sql)?;
+    if user.mfa_enabled_at.is_none() {
+        return Err(Errors::conflict("mfa is not enabled"));
+    }
+
+    replace_recovery_codes(&mfa_repo, &user).await.map(Json)
+}
+
+async fn replace_recovery_codes(
+    mfa_repo: &MfaRepository,
+    user: &User,
+) -> Result<RecoveryCodes, (StatusCode, Json<Errors>)> {
+    let codes = mfa::recovery_codes();
+    let mut hashes = Vec::new();
+    for code in &codes {
+        hashes.push(mfa::hash_recovery_code(code).map_err(Errors::argon2)?);
+    }
+
+    mfa_repo
+        .replace_recovery_codes(user.id, hashes)
+        .await
+        .map_err(Errors::sql)?;
+    Ok(RecoveryCodes {
+        recovery_codes: codes,
+    })
+}
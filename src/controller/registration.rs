@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+
+use crate::{
+    mail::Mailer,
+    model::{RegisterDto, UserCreateDto, UserWithGroups},
+    repository::{
+        EmailVerificationRepository, GroupRepository, OrganizationRepository, UserRepository,
+    },
+    security::password,
+    state::AppState,
+};
+
+use super::{email_verification, Errors};
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/", post(register))
+}
+
+/// Returns true if `REGISTRATION_ENABLED` turns on `POST /register`.
+pub fn enabled() -> bool {
+    std::env::var("REGISTRATION_ENABLED").is_ok_and(|value| value == "true")
+}
+
+/// Returns true if self registered users can only log in after verifying
+/// their email address, set with `REGISTRATION_REQUIRE_EMAIL_VERIFICATION`.
+pub fn requires_email_verification() -> bool {
+    std::env::var("REGISTRATION_REQUIRE_EMAIL_VERIFICATION").is_ok_and(|value| value == "true")
+}
+
+/// Returns the invite codes from `REGISTRATION_INVITE_CODES`, registration
+/// requires one of them when the list is not empty.
+fn invite_codes() -> Vec<String> {
+    std::env::var("REGISTRATION_INVITE_CODES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|code| code.trim())
+        .filter(|code| !code.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Creates a user in the group named by `REGISTRATION_DEFAULT_GROUP`
+/// (`nobody` by default), in the system organization or the one named by
+/// `organization` if it allows registration. Registrants can not choose
+/// their flags or groups.
+///
+/// # Errors
+///
+/// * `forbidden` - if an invite code is required and missing or incorrect, or
+///   the organization does not allow registration
+/// * `unprocessable_entity` - if the password does not follow the password policy
+/// * `internal_error` - if there was a problem with the database or password hashing
+#[axum::debug_handler(state = AppState)]
+pub async fn register(
+    State(repo): State<UserRepository>,
+    State(groups): State<GroupRepository>,
+    State(organizations): State<OrganizationRepository>,
+    State(verifications): State<EmailVerificationRepository>,
+    State(mailer): State<Arc<dyn Mailer>>,
+    Json(dto): Json<RegisterDto>,
+) -> Result<Json<UserWithGroups>, (StatusCode, Json<Errors>)> {
+    let codes = invite_codes();
+    if !codes.is_empty() && !dto.invite_code.is_some_and(|code| codes.contains(&code)) {
+        return Err(Errors::forbidden());
+    }
+    password::validate(&dto.password).map_err(|err| Errors::unprocessable(&err))?;
+
+    let org_id = organizations
+        .resolve(dto.organization.as_deref())
+        .await
+        .map_err(|_| Errors::unprocessable("unknown organization"))?;
+    if !organizations
+        .allows_registration(org_id)
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Err(Errors::forbidden());
+    }
+    let group_name = std::env::var("REGISTRATION_DEFAULT_GROUP").unwrap_or(String::from("nobody"));
+    let group = groups
+        .find_by_name(org_id, &group_name)
+        .await
+        .map_err(|err| Errors::internal(&format!("default group {}: {}", group_name, err)))?;
+
+    let password_hash = password::hash(&dto.password).map_err(Errors::argon2)?;
+    let dto = UserCreateDto {
+        name: dto.name,
+        phone: dto.phone,
+        role: None,
+        email: dto.email,
+        username: dto.username,
+        password: dto.password,
+        visible: true,
+        editable: true,
+        locked: false,
+        password_hash,
+        attributes: None,
+        groups: vec![group.id],
+        email_verified: false,
+        registered: true,
+    };
+    let user = repo.create(org_id, dto).await.map_err(Errors::sql)?;
+
+    email_verification::send_or_log(&verifications, &mailer, user.user.id, &user.user.email).await;
+    Ok(Json(user))
+}
@@ -1,56 +1,100 @@
+use std::sync::Arc;
+
 use crate::{
+    mail::Mailer,
+    model::{Grant, GrantDto, Group, ListQuery, Session, USER_RESOURCE},
     model::{PasswordDto, UserUpdateDto},
-    security::{password, Jwt},
+    repository::{
+        EmailVerificationRepository, GrantRepository, GroupRepository, PermissionRepository,
+        SessionRepository, SoftDelete,
+    },
+    security::{
+        password,
+        policy::{self, PolicyEngine},
+        scope, ClientInfo, Jwt,
+    },
 };
 use axum::{
-    extract::{Path, State},
+    extract::{FromRef, Path, Query, State},
     http::StatusCode,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use serde_json::Value;
 use uuid::Uuid;
 
 use crate::{
     model::{UserCreateDto, UserWithGroups},
     repository::UserRepository,
+    state::AppState,
 };
 
-use super::Errors;
+use super::{access, email_verification, membership, Errors};
 
-pub fn routes(repo: UserRepository) -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/", get(index))
         .route("/:id", get(show))
         .route("/", post(create))
         .route("/:id", put(update))
+        .route("/:id", delete(destroy))
+        .route("/:id/restore", post(restore))
         .route("/:id/password", put(update_password))
-        .with_state(repo)
+        .route("/:id/sign-out", post(sign_out))
+        .route("/:id/sessions", get(sessions))
+        .route("/:id/sessions/:session_id", delete(revoke_session))
+        .route("/:id/access", get(access))
+        .route("/:id/access", post(grant))
+        .route("/:id/access/:grant_id", delete(revoke_grant))
+        .route("/:id/groups", get(groups))
+        .route("/:id/groups/:group_id", put(join_group))
+        .route("/:id/groups/:group_id", delete(leave_group))
 }
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn index(
     State(repo): State<UserRepository>,
+    State(policies): State<PolicyEngine>,
     jwt: Jwt,
+    client: ClientInfo,
+    Query(query): Query<ListQuery>,
 ) -> Result<Json<Vec<UserWithGroups>>, (StatusCode, Json<Errors>)> {
-    if !jwt.has_permission("user:read") {
+    let granted = jwt.has_permission("user:read");
+    let checked = policies.applies_to("user:read");
+    if (!granted && !checked) || (query.include_deleted && !jwt.is_admin()) {
         return Err(Errors::forbidden());
     }
 
-    repo.find_all_with_groups()
+    let mut users = repo
+        .find_all_with_groups(jwt.org_id, query.include_deleted)
         .await
-        .map(Json)
-        .map_err(Errors::sql)
+        .map_err(Errors::sql)?;
+    if !jwt.is_root() {
+        users.retain(|user| user.user.visible);
+    }
+    if checked {
+        let subject = subject(&jwt, &repo).await?;
+        let context = policies.context(&client);
+        users.retain(|user| {
+            let attributes =
+                policy::attributes(subject.clone(), policy::user(user), context.clone());
+            policies.check("user:read", &jwt.perms, granted, &attributes)
+        });
+    }
+    Ok(Json(users))
 }
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn show(
     jwt: Jwt,
+    client: ClientInfo,
     State(repo): State<UserRepository>,
+    State(policies): State<PolicyEngine>,
+    State(grants): State<GrantRepository>,
     Path(id): Path<Uuid>,
 ) -> Result<Json<UserWithGroups>, (StatusCode, Json<Errors>)> {
-    if !jwt.has_permission("user:read") {
-        return Err(Errors::forbidden());
-    }
+    authorize(&jwt, &client, &policies, &repo, &grants, "user:read", id).await?;
+    check_access(&jwt, &repo, id, false).await?;
 
     repo.find_with_groups(id)
         .await
@@ -58,16 +102,21 @@ pub async fn show(
         .map_err(Errors::sql)
 }
 
-#[axum::debug_handler]
+#[axum::debug_handler(state = AppState)]
 pub async fn create(
     jwt: Jwt,
     State(repo): State<UserRepository>,
+    State(groups): State<GroupRepository>,
+    State(verifications): State<EmailVerificationRepository>,
+    State(mailer): State<Arc<dyn Mailer>>,
     Json(dto): Json<UserCreateDto>,
 ) -> Result<Json<UserWithGroups>, (StatusCode, Json<Errors>)> {
-    if !jwt.has_permission("user:create") {
+    if !jwt.has_permission("user:create") || (dto.attributes.is_some() && !jwt.is_admin()) {
         return Err(Errors::forbidden());
     }
+    check_groups(&jwt, &groups, &dto.groups).await?;
 
+    password::validate(&dto.password).map_err(|err| Errors::unprocessable(&err))?;
     let password_hash = password::hash(&dto.password).map_err(Errors::argon2)?;
     let (visible, editable) = if jwt.is_root() {
         (dto.visible, dto.editable)
@@ -83,40 +132,466 @@ pub async fn create(
         locked,
         ..dto
     };
-    repo.create(dto).await.map(Json).map_err(Errors::sql)
+    let user = repo.create(jwt.org_id, dto).await.map_err(Errors::sql)?;
+    email_verification::send_or_log(&verifications, &mailer, user.user.id, &user.user.email).await;
+    Ok(Json(user))
 }
 
-#[axum::debug_handler]
+/// Updates a user, a new email address stays pending until it is verified.
+/// Only root can update non-editable users or change the visible and
+/// editable flags, only admins can lock users or change the attributes of
+/// others, and only callers who could grant every permission of the user can
+/// update it.
+#[axum::debug_handler(state = AppState)]
+#[allow(clippy::too_many_arguments)]
 pub async fn update(
+    jwt: Jwt,
+    client: ClientInfo,
     State(repo): State<UserRepository>,
+    State(groups): State<GroupRepository>,
+    State(policies): State<PolicyEngine>,
+    State(grants): State<GrantRepository>,
+    State(verifications): State<EmailVerificationRepository>,
+    State(mailer): State<Arc<dyn Mailer>>,
     Path(id): Path<Uuid>,
     Json(dto): Json<UserUpdateDto>,
-) -> Result<Json<UserWithGroups>, (StatusCode, String)> {
-    match repo.update(id, dto).await {
-        Ok(data) => Ok(Json(data)),
-        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+) -> Result<Json<UserWithGroups>, (StatusCode, Json<Errors>)> {
+    authorize(&jwt, &client, &policies, &repo, &grants, "user:update", id).await?;
+    check_access(&jwt, &repo, id, true).await?;
+    check_privileges(&jwt, &repo, &groups, id).await?;
+    // the policies trust the attributes, e.g. the department of the caller
+    if let Some(attributes) = &dto.attributes {
+        let user = repo.find(id).await.map_err(Errors::sql)?;
+        if *attributes != user.attributes.0 && (!jwt.is_admin() || id == jwt.id) {
+            return Err(Errors::forbidden());
+        }
+    }
+
+    // groups the user already belongs to are kept as they are
+    let current = repo.group_ids(id).await.map_err(Errors::sql)?;
+    let added: Vec<Uuid> = dto
+        .groups
+        .iter()
+        .filter(|group| !current.contains(group))
+        .cloned()
+        .collect();
+    check_groups(&jwt, &groups, &added).await?;
+
+    let (visible, editable) = if jwt.is_root() {
+        (dto.visible, dto.editable)
+    } else {
+        (true, true)
+    };
+    let locked = if jwt.is_admin() {
+        dto.locked
+    } else {
+        repo.is_locked(id).await.map_err(Errors::sql)?
+    };
+
+    let dto = UserUpdateDto {
+        visible,
+        editable,
+        locked,
+        ..dto
+    };
+    let email = dto.email.clone();
+    let data = repo
+        .update(jwt.org_id, id, dto)
+        .await
+        .map_err(Errors::sql)?
+        .ok_or_else(Errors::last_root)?;
+    if data.user.pending_email.as_ref() == Some(&email) {
+        email_verification::send_or_log(&verifications, &mailer, id, &email).await;
     }
+    Ok(Json(data))
 }
 
-#[axum::debug_handler]
+/// Soft-deletes a user, its tokens are revoked and it can not sign in until
+/// it is restored.
+///
+/// # Errors
+///
+/// * `last_root` - if the user is the last active root user
+#[axum::debug_handler(state = AppState)]
+pub async fn destroy(
+    jwt: Jwt,
+    client: ClientInfo,
+    State(repo): State<UserRepository>,
+    State(policies): State<PolicyEngine>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    authorize(&jwt, &client, &policies, &repo, &grants, "user:delete", id).await?;
+    check_access(&jwt, &repo, id, true).await?;
+
+    let deleted = repo.delete(jwt.org_id, id).await.map_err(Errors::sql)?;
+    if !deleted {
+        return Err(Errors::last_root());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Restores a deleted user with its groups and grants, only for callers who
+/// could grant every permission it gets back.
+///
+/// # Errors
+///
+/// * `not_found` - if the user is not deleted
+/// * `forbidden` - if the caller could not grant a permission of its groups
+/// * `conflict` - if another user took its username or email meanwhile
+#[axum::debug_handler(state = AppState)]
+pub async fn restore(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(groups): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<UserWithGroups>, (StatusCode, Json<Errors>)> {
+    if !scope::allows(&jwt, &grants, USER_RESOURCE, id, "user:delete")
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Err(Errors::forbidden());
+    }
+    // deleted users are hidden from `check_access`
+    if !jwt.is_root() {
+        if !repo.is_visible(id).await.map_err(Errors::sql)? {
+            return Err(Errors::not_found());
+        }
+        if !repo.is_editable(id).await.map_err(Errors::sql)? {
+            return Err(Errors::forbidden());
+        }
+        let ids = repo.restored_group_ids(id).await.map_err(Errors::sql)?;
+        check_grantable(&jwt, &groups, &ids).await?;
+    }
+
+    repo.restore(jwt.org_id, id)
+        .await
+        .map_err(|err| match err {
+            sqlx::Error::Database(err) if err.is_unique_violation() => {
+                Errors::conflict("username or email is taken")
+            }
+            err => Errors::sql(err),
+        })?;
+    repo.find_with_groups(id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+/// Policies and grants `authorize` checks, extracted together.
+#[derive(Clone)]
+pub struct Authorization {
+    policies: PolicyEngine,
+    grants: GrantRepository,
+}
+
+impl FromRef<AppState> for Authorization {
+    fn from_ref(state: &AppState) -> Self {
+        Authorization {
+            policies: state.policies.clone(),
+            grants: state.grants.clone(),
+        }
+    }
+}
+
+/// Sets the password of a user. Users may set their own, the password of
+/// another user only callers who could grant every permission of the user.
+#[axum::debug_handler(state = AppState)]
 pub async fn update_password(
+    jwt: Jwt,
+    client: ClientInfo,
     State(repo): State<UserRepository>,
+    State(groups): State<GroupRepository>,
+    State(auth): State<Authorization>,
     Path(id): Path<Uuid>,
     Json(dto): Json<PasswordDto>,
-) -> Result<StatusCode, (StatusCode, String)> {
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    if jwt.id != id {
+        let Authorization { policies, grants } = &auth;
+        authorize(&jwt, &client, policies, &repo, grants, "user:update", id).await?;
+        check_access(&jwt, &repo, id, true).await?;
+        check_privileges(&jwt, &repo, &groups, id).await?;
+    }
+
+    password::validate(&dto.password).map_err(|err| Errors::unprocessable(&err))?;
     // hash plain password with argon2
-    let hash = match password::hash(&dto.password) {
-        Ok(hash) => hash,
-        Err(err) => return Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
-    };
+    let hash = password::hash(&dto.password).map_err(Errors::argon2)?;
 
     let dto = PasswordDto {
         password_hash: hash,
         ..dto
     };
 
-    match repo.update_password(id, dto).await {
-        Ok(()) => Ok(StatusCode::NO_CONTENT),
-        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    repo.update_password(id, dto)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+/// Signs a user out everywhere by invalidating every token issued to it.
+#[axum::debug_handler(state = AppState)]
+pub async fn sign_out(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, USER_RESOURCE, id, "user:update").await?;
+    check_access(&jwt, &repo, id, true).await?;
+
+    repo.bump_token_version(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn sessions(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(sessions): State<SessionRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Session>>, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, USER_RESOURCE, id, "user:read").await?;
+    check_access(&jwt, &repo, id, false).await?;
+
+    sessions
+        .find_active(id)
+        .await
+        .map(Json)
+        .map_err(Errors::sql)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn revoke_session(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(sessions): State<SessionRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, session_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    access::authorize(&jwt, &grants, USER_RESOURCE, id, "user:update").await?;
+    check_access(&jwt, &repo, id, true).await?;
+
+    sessions
+        .revoke(session_id, id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+/// Groups the user is a direct member of.
+#[axum::debug_handler(state = AppState)]
+pub async fn groups(
+    jwt: Jwt,
+    client: ClientInfo,
+    State(repo): State<UserRepository>,
+    State(policies): State<PolicyEngine>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Group>>, (StatusCode, Json<Errors>)> {
+    authorize(&jwt, &client, &policies, &repo, &grants, "user:read", id).await?;
+    check_access(&jwt, &repo, id, false).await?;
+
+    repo.groups(id).await.map(Json).map_err(Errors::sql)
+}
+
+/// Adds the user to a group, with the same checks as adding a member to the
+/// group.
+#[axum::debug_handler(state = AppState)]
+pub async fn join_group(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(groups): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, group_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    membership::check_add(&jwt, &groups, &repo, &grants, group_id, &[id]).await?;
+
+    repo.add_to_group(id, group_id, None, None)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(Errors::sql)
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn leave_group(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(groups): State<GroupRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, group_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    membership::check_remove(&jwt, &groups, &repo, &grants, group_id, &[id]).await?;
+
+    let removed = repo
+        .remove_from_group(jwt.org_id, id, group_id)
+        .await
+        .map_err(Errors::sql)?;
+    if !removed {
+        return Err(Errors::last_root());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists who was given access to the user.
+#[axum::debug_handler(state = AppState)]
+pub async fn access(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<Grant>>, (StatusCode, Json<Errors>)> {
+    check_access(&jwt, &repo, id, false).await?;
+    access::list(&jwt, &grants, USER_RESOURCE, id).await
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn grant(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    State(permissions): State<PermissionRepository>,
+    Path(id): Path<Uuid>,
+    Json(dto): Json<GrantDto>,
+) -> Result<Json<Grant>, (StatusCode, Json<Errors>)> {
+    check_access(&jwt, &repo, id, true).await?;
+    access::grant(&jwt, &grants, &permissions, USER_RESOURCE, id, dto).await
+}
+
+#[axum::debug_handler(state = AppState)]
+pub async fn revoke_grant(
+    jwt: Jwt,
+    State(repo): State<UserRepository>,
+    State(grants): State<GrantRepository>,
+    Path((id, grant_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, (StatusCode, Json<Errors>)> {
+    check_access(&jwt, &repo, id, true).await?;
+    access::revoke(&jwt, &grants, USER_RESOURCE, id, grant_id).await
+}
+
+/// Checks `permission` on a user with the permissions of the caller, its
+/// grants on the user and the policies, the users are only loaded when a
+/// policy applies.
+async fn authorize(
+    jwt: &Jwt,
+    client: &ClientInfo,
+    policies: &PolicyEngine,
+    repo: &UserRepository,
+    grants: &GrantRepository,
+    permission: &str,
+    id: Uuid,
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    let granted = scope::allows(jwt, grants, USER_RESOURCE, id, permission)
+        .await
+        .map_err(Errors::sql)?;
+    let allowed = if policies.applies_to(permission) {
+        let resource = repo.find_with_groups(id).await.map_err(Errors::sql)?;
+        let attributes = policy::attributes(
+            subject(jwt, repo).await?,
+            policy::user(&resource),
+            policies.context(client),
+        );
+        policies.check(permission, &jwt.perms, granted, &attributes)
+    } else {
+        granted
+    };
+    if !allowed {
+        return Err(Errors::forbidden());
+    }
+    Ok(())
+}
+
+async fn subject(jwt: &Jwt, repo: &UserRepository) -> Result<Value, (StatusCode, Json<Errors>)> {
+    let caller = repo.find_with_groups(jwt.id).await.map_err(Errors::sql)?;
+    Ok(policy::subject(&caller, &jwt.perms))
+}
+
+/// Hides non-visible users from everyone but root, and only lets root modify
+/// non-editable users.
+async fn check_access(
+    jwt: &Jwt,
+    repo: &UserRepository,
+    id: Uuid,
+    modify: bool,
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    if !repo
+        .in_organization(id, jwt.org_id)
+        .await
+        .map_err(Errors::sql)?
+    {
+        return Err(Errors::not_found());
+    }
+    if jwt.is_root() {
+        return Ok(());
+    }
+    if !repo.is_visible(id).await.map_err(Errors::sql)? {
+        return Err(Errors::not_found());
+    }
+    if modify && !repo.is_editable(id).await.map_err(Errors::sql)? {
+        return Err(Errors::forbidden());
+    }
+    Ok(())
+}
+
+/// Refuses to let the caller take over a user with permissions it could not
+/// grant itself, e.g. by resetting the password or email of an admin.
+async fn check_privileges(
+    jwt: &Jwt,
+    repo: &UserRepository,
+    groups: &GroupRepository,
+    id: Uuid,
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    if jwt.is_root() {
+        return Ok(());
+    }
+    let ids = repo.group_ids(id).await.map_err(Errors::sql)?;
+    check_grantable(jwt, groups, &ids).await
+}
+
+/// Refuses callers who could not grant every permission of the groups,
+/// including the permissions inherited from the groups containing them.
+async fn check_grantable(
+    jwt: &Jwt,
+    groups: &GroupRepository,
+    ids: &[Uuid],
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    let groups = groups.find_with_ancestors(ids).await.map_err(Errors::sql)?;
+    let mut permissions = groups.iter().flat_map(|group| group.permissions.iter());
+    if !permissions.all(|p| jwt.can_grant(p)) {
+        return Err(Errors::forbidden());
+    }
+    Ok(())
+}
+
+/// Refuses groups of other organizations and groups granting permissions the
+/// caller could not grant itself, including the permissions inherited from
+/// the groups containing them.
+async fn check_groups(
+    jwt: &Jwt,
+    groups: &GroupRepository,
+    ids: &[Uuid],
+) -> Result<(), (StatusCode, Json<Errors>)> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let groups = groups.find_with_ancestors(ids).await.map_err(Errors::sql)?;
+    let known = |id: &Uuid| {
+        groups
+            .iter()
+            .any(|group| group.id == *id && group.org_id == jwt.org_id)
+    };
+    if !ids.iter().all(known) {
+        return Err(Errors::unprocessable("unknown group"));
+    }
+    if jwt.is_root() {
+        return Ok(());
+    }
+    let mut permissions = groups.iter().flat_map(|group| group.permissions.iter());
+    if !permissions.all(|p| jwt.can_grant(p)) {
+        return Err(Errors::forbidden());
     }
+    Ok(())
 }
@@ -0,0 +1,11 @@
+//! Authentication and authorization service, the `gaia-auth` binary serves
+//! the routes of `controller::routes` and `gaia-auth-client` verifies its
+//! tokens in other services.
+
+pub mod controller;
+pub mod mail;
+pub mod model;
+pub mod repository;
+pub mod security;
+pub mod seed;
+pub mod state;
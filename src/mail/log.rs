@@ -0,0 +1,14 @@
+use axum::async_trait;
+
+use super::Mailer;
+
+/// Prints messages to stdout instead of sending them, for development.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        println!("mail to {}: {}\n{}", to, subject, body);
+        Ok(())
+    }
+}
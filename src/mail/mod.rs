@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+
+mod log;
+mod smtp;
+
+pub use log::LogMailer;
+pub use smtp::SmtpMailer;
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String>;
+}
+
+/// Builds the mailer selected by `MAIL_TRANSPORT`, `log` (default) or `smtp`.
+pub fn from_env() -> Arc<dyn Mailer> {
+    let transport = std::env::var("MAIL_TRANSPORT").unwrap_or(String::from("log"));
+    match transport.as_str() {
+        "log" => Arc::new(LogMailer),
+        "smtp" => Arc::new(SmtpMailer::from_env()),
+        _ => panic!("unknown MAIL_TRANSPORT: {}", transport),
+    }
+}
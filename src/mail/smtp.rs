@@ -0,0 +1,51 @@
+use axum::async_trait;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::Mailer;
+
+pub struct SmtpMailer {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpMailer {
+    pub fn from_env() -> Self {
+        let host = std::env::var("SMTP_HOST").expect("SMTP_HOST must be set");
+        let from = std::env::var("MAIL_FROM").expect("MAIL_FROM must be set");
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(&host)
+            .expect("failed to create smtp transport");
+        if let Ok(port) = std::env::var("SMTP_PORT") {
+            builder = builder.port(port.parse().expect("SMTP_PORT must be a number"));
+        }
+        if let (Ok(user), Ok(pass)) = (std::env::var("SMTP_USER"), std::env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(user, pass));
+        }
+        SmtpMailer {
+            from: from.parse().expect("MAIL_FROM must be a valid address"),
+            transport: builder.build(),
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), String> {
+        let to: Mailbox = to
+            .parse()
+            .map_err(|err: lettre::address::AddressError| err.to_string())?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .body(String::from(body))
+            .map_err(|err| err.to_string())?;
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    }
+}
@@ -1,16 +1,18 @@
-use axum::Router;
-use controller::{auth_controller, group_controller, profile, user_controller};
+use std::net::SocketAddr;
+
 use dotenvy::dotenv;
-use model::{GroupDto, UserCreateDto};
-use repository::{GroupRepository, UserRepository};
+use gaia_auth::{
+    controller,
+    repository::PolicyRepository,
+    security::{
+        self,
+        policy::{self, PolicyEngine},
+    },
+    state::AppState,
+};
 use sqlx::{Pool, Postgres};
 use tokio::net::TcpListener;
 
-mod controller;
-mod model;
-mod repository;
-mod security;
-
 #[tokio::main]
 async fn main() {
     dotenv().ok();
@@ -23,7 +25,7 @@ async fn main() {
     // run migrations
     migrate(db.clone()).await;
     // seed database
-    seed(db.clone()).await;
+    gaia_auth::seed::run(db.clone()).await;
     // start http server
     http(db).await;
 }
@@ -49,98 +51,21 @@ async fn migrate(db: Pool<Postgres>) {
         .expect("failed to run migrations");
 }
 
-async fn seed(db: Pool<Postgres>) {
-    init_system(db).await;
-}
-
-async fn init_system(db: Pool<Postgres>) {
-    let repo = GroupRepository::new(db.clone());
-    let urepo = UserRepository::new(db);
-    if repo.count().await.unwrap() > 0 {
-        return;
-    }
-    let dto = GroupDto {
-        name: String::from("root"),
-        description: Some(String::from("super users group")),
-        permissions: vec![String::from("root")],
-        visible: Some(false),
-        editable: Some(false),
-        locked: Some(true),
-    };
-    match repo.create(dto).await {
-        Ok(group) => {
-            let hash = security::password::hash("root").expect("failed to hash password");
-            let dto = UserCreateDto {
-                name: String::from("root"),
-                phone: None,
-                role: None,
-                email: String::from("root@change.me"),
-                username: String::from("root"),
-                password: String::from("root"),
-                password_hash: hash,
-                visible: false,
-                editable: false,
-                locked: true,
-                groups: vec![group.id],
-            };
-            if let Err(e) = urepo.create(dto).await {
-                panic!("failed to create root user: {}", e);
-            }
-        }
-        Err(e) => {
-            panic!("failed to create root group: {}", e);
-        }
-    }
-
-    let dto = GroupDto {
-        name: String::from("admin"),
-        description: Some(String::from("admin users group")),
-        permissions: vec![String::from("admin")],
-        visible: Some(true),
-        editable: Some(false),
-        locked: Some(true),
-    };
-    match repo.create(dto).await {
-        Ok(group) => {
-            let hash = security::password::hash("admin").expect("failed to hash password");
-            let dto = UserCreateDto {
-                name: String::from("admin"),
-                phone: None,
-                role: None,
-                email: String::from("admin@change.me"),
-                username: String::from("admin"),
-                password: String::from("admin"),
-                password_hash: hash,
-                visible: true,
-                editable: false,
-                locked: true,
-                groups: vec![group.id],
-            };
-            if let Err(e) = urepo.create(dto).await {
-                panic!("failed to create admin user: {}", e);
-            }
-        }
-        Err(e) => {
-            panic!("failed to create admin group: {}", e);
-        }
-    }
-
-    let dto = GroupDto {
-        name: String::from("nobody"),
-        description: Some(String::from("nobody users group")),
-        permissions: vec![String::from("nobody")],
-        visible: Some(false),
-        editable: Some(false),
-        locked: Some(true),
-    };
-    if let Err(e) = repo.create(dto).await {
-        panic!("failed to create nobody group: {}", e);
-    }
+async fn policies(db: Pool<Postgres>) -> PolicyEngine {
+    let mut rules = policy::from_file();
+    let repo = PolicyRepository::new(db);
+    rules.extend(repo.find_enabled().await.expect("failed to load policies"));
+    PolicyEngine::new(rules)
 }
 
 async fn http(db: Pool<Postgres>) {
-    let group_repo = GroupRepository::new(db.clone());
-    let user_repo = UserRepository::new(db);
+    let policies = policies(db.clone()).await;
+    security::forward_auth::init();
+    security::client::init();
+    security::verification::init();
+    security::password::init();
+    security::token_version::init();
+    let state = AppState::new(db, policies);
 
     let host = std::env::var("HTTP_HOST").unwrap_or(String::from("0.0.0.0"));
     let port = std::env::var("HTTP_PORT").unwrap_or(String::from("4000"));
@@ -149,10 +74,9 @@ async fn http(db: Pool<Postgres>) {
     let tcp = TcpListener::bind(addr)
         .await
         .expect("failed to bind to address");
-    let app = Router::new()
-        .nest("/groups", group_controller::routes(group_repo))
-        .nest("/users", user_controller::routes(user_repo.clone()))
-        .nest("/profile", profile::routes(user_repo.clone()))
-        .nest("/", auth_controller::router(user_repo));
-    axum::serve(tcp, app).await.expect("failed to start server");
+    let app = controller::routes().with_state(state);
+    // the peer address is recorded on sessions when there is no proxy
+    axum::serve(tcp, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .expect("failed to start server");
 }
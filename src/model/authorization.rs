@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Resource a permission is checked on, its grants apply and its attributes
+/// are given to the policies.
+#[derive(Debug, Deserialize)]
+pub struct ResourceDto {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub id: Uuid,
+    #[serde(default)]
+    pub attributes: Map<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckDto {
+    pub permission: String,
+    pub resource: Option<ResourceDto>,
+}
+
+/// Permission checked for the holder of `token`, or for the user `subject`
+/// when the caller may check the permissions of others.
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeDto {
+    pub token: Option<String>,
+    pub subject: Option<Uuid>,
+    #[serde(flatten)]
+    pub check: CheckDto,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuthorizeBatchDto {
+    pub token: Option<String>,
+    pub subject: Option<Uuid>,
+    pub checks: Vec<CheckDto>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorizationDecision {
+    pub permission: String,
+    pub allowed: bool,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthorizationDecisions {
+    pub subject: Option<Uuid>,
+    pub decisions: Vec<AuthorizationDecision>,
+}
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+/// Resource types grants can be given on.
+pub const USER_RESOURCE: &str = "user";
+pub const GROUP_RESOURCE: &str = "group";
+
+/// Permission on a single resource, given to a user or to the members of a
+/// group.
+#[derive(Debug, Serialize, FromRow)]
+pub struct Grant {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub group_id: Option<Uuid>,
+    pub permission: String,
+    pub resource_type: String,
+    pub resource_id: Uuid,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrantDto {
+    pub user_id: Option<Uuid>,
+    pub group_id: Option<Uuid>,
+    pub permission: String,
+}
@@ -2,15 +2,22 @@ use serde::{Deserialize, Serialize};
 use sqlx::{prelude::FromRow, types::Json};
 use uuid::Uuid;
 
+use super::SessionPolicy;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Group {
     pub id: Uuid,
+    pub org_id: Uuid,
     pub name: String,
     pub description: Option<String>,
     pub permissions: Json<Vec<String>>,
     pub visible: bool,
     pub editable: bool,
     pub locked: bool,
+    pub require_mfa: bool,
+    pub allow_passwordless: bool,
+    pub idle_timeout: Option<i32>,
+    pub max_sessions: Option<i32>,
     pub created_at: i64,
     pub updated_at: i64,
     pub deleted_at: Option<i64>,
@@ -24,9 +31,56 @@ pub struct GroupDto {
     pub visible: Option<bool>,
     pub editable: Option<bool>,
     pub locked: Option<bool>,
+    pub require_mfa: Option<bool>,
+    pub allow_passwordless: Option<bool>,
+    pub idle_timeout: Option<i32>,
+    pub max_sessions: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemberDto {
+    pub user_id: Uuid,
+    /// Start and end of the membership as epoch seconds, unbounded when
+    /// omitted.
+    pub valid_from: Option<i64>,
+    pub valid_until: Option<i64>,
+}
+
+/// Members added to and removed from a group at once.
+#[derive(Debug, Deserialize)]
+pub struct MembersPatchDto {
+    #[serde(default)]
+    pub add: Vec<Uuid>,
+    #[serde(default)]
+    pub remove: Vec<Uuid>,
 }
 
 impl Group {
+    /// Returns true if any of the groups requires its members to use MFA.
+    pub fn requires_mfa<'a>(mut groups: impl Iterator<Item = &'a Group>) -> bool {
+        groups.any(|group| group.require_mfa)
+    }
+
+    /// Returns true if any of the groups allows its members to log in with
+    /// emailed links or codes.
+    pub fn allows_passwordless<'a>(mut groups: impl Iterator<Item = &'a Group>) -> bool {
+        groups.any(|group| group.allow_passwordless)
+    }
+
+    /// Returns the session policy of a member of the groups, taking the
+    /// strictest value when several groups set the same limit.
+    pub fn session_policy<'a>(groups: impl Iterator<Item = &'a Group> + Clone) -> SessionPolicy {
+        SessionPolicy {
+            idle_timeout: groups.clone().filter_map(|group| group.idle_timeout).min(),
+            max_sessions: groups.filter_map(|group| group.max_sessions).min(),
+        }
+    }
+
+    /// Returns true if the group grants the `root` permission.
+    pub fn is_root(&self) -> bool {
+        self.permissions.iter().any(|p| p == "root")
+    }
+
     pub fn permissions(&self) -> Vec<String> {
         let mut list = Vec::new();
         for permission in self.permissions.iter() {
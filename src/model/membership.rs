@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+/// Statuses of a membership request.
+pub const REQUEST_PENDING: &str = "pending";
+pub const REQUEST_APPROVED: &str = "approved";
+pub const REQUEST_REJECTED: &str = "rejected";
+
+/// Temporary membership in a group asked by a user, an owner of the group
+/// approves or rejects it.
+#[derive(Debug, Serialize, FromRow)]
+pub struct MembershipRequest {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub group_id: Uuid,
+    pub reason: Option<String>,
+    pub valid_from: Option<i64>,
+    pub valid_until: i64,
+    pub status: String,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MembershipRequestDto {
+    pub reason: Option<String>,
+    pub valid_from: Option<i64>,
+    pub valid_until: i64,
+}
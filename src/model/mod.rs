@@ -1,7 +1,30 @@
+mod authorization;
+mod grant;
 mod group;
+mod membership;
+mod organization;
+mod permission;
+mod query;
 mod security;
+mod session;
 mod user;
 
-pub use group::{Group, GroupDto};
-pub use security::{LoginDto, PasswordDto};
-pub use user::{ProfileDto, User, UserCreateDto, UserUpdateDto, UserWithGroups};
+pub use authorization::{
+    AuthorizationDecision, AuthorizationDecisions, AuthorizeBatchDto, AuthorizeDto, CheckDto,
+};
+pub use grant::{Grant, GrantDto, GROUP_RESOURCE, USER_RESOURCE};
+pub use group::{Group, GroupDto, MemberDto, MembersPatchDto};
+pub use membership::{
+    MembershipRequest, MembershipRequestDto, REQUEST_APPROVED, REQUEST_PENDING, REQUEST_REJECTED,
+};
+pub use organization::{Organization, OrganizationDto, SYSTEM_ORGANIZATION};
+pub use permission::{Permission, PermissionDto, RegisterPermissionsDto};
+pub use query::ListQuery;
+pub use security::{
+    LoginCodeDto, LoginDto, LoginLinkDto, MfaLoginDto, PasswordDto, PasswordlessDto,
+    PasswordlessMethod, VerifyEmailDto,
+};
+pub use session::{Session, SessionPolicy};
+pub use user::{
+    EmailDto, ProfileDto, RegisterDto, User, UserCreateDto, UserUpdateDto, UserWithGroups,
+};
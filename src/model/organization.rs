@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+/// Organization existing users and groups were moved to, its super-admins
+/// work across organizations.
+pub const SYSTEM_ORGANIZATION: Uuid = Uuid::nil();
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Users may register themselves into the organization, see
+    /// `POST /register`.
+    pub allow_registration: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrganizationDto {
+    pub name: String,
+    pub description: Option<String>,
+    /// Omitted it is off for new organizations and kept on update.
+    pub allow_registration: Option<bool>,
+}
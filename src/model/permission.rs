@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Permission {
+    pub name: String,
+    pub description: Option<String>,
+    /// Service that registered the permission, none for permissions that
+    /// were granted before any service registered them.
+    pub service: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PermissionDto {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Permissions a service checks, registered when it starts.
+#[derive(Debug, Deserialize)]
+pub struct RegisterPermissionsDto {
+    pub service: String,
+    pub permissions: Vec<PermissionDto>,
+}
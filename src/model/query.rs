@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+/// Query of the user and group listings.
+#[derive(Debug, Deserialize)]
+pub struct ListQuery {
+    /// Also lists the deleted rows, only for admins.
+    #[serde(default)]
+    pub include_deleted: bool,
+}
@@ -11,4 +11,57 @@ pub struct PasswordDto {
 pub struct LoginDto {
     pub username: String,
     pub password: String,
+    /// Name of the organization of the user, the system organization when
+    /// omitted.
+    pub organization: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MfaLoginDto {
+    pub mfa_token: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailDto {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordlessMethod {
+    Link,
+    Code,
+}
+
+impl PasswordlessMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PasswordlessMethod::Link => "link",
+            PasswordlessMethod::Code => "code",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordlessDto {
+    pub email: String,
+    pub method: PasswordlessMethod,
+    /// Name of the organization of the user, the system organization when
+    /// omitted.
+    pub organization: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginLinkDto {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginCodeDto {
+    pub email: String,
+    pub code: String,
+    /// Name of the organization of the user, the system organization when
+    /// omitted.
+    pub organization: Option<String>,
 }
@@ -0,0 +1,25 @@
+use serde::Serialize;
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub device: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    pub revoked_at: Option<i64>,
+    pub idle_timeout: Option<i32>,
+}
+
+/// Limits on the sessions of a user, set by its groups.
+#[derive(Debug, Default)]
+pub struct SessionPolicy {
+    /// Minutes of inactivity after which a session expires.
+    pub idle_timeout: Option<i32>,
+    /// Maximum number of active sessions, the oldest ones are revoked.
+    pub max_sessions: Option<i32>,
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use serde_json::{Map, Value};
+use sqlx::{prelude::FromRow, types::Json};
 use uuid::Uuid;
 
 use super::Group;
@@ -7,16 +8,26 @@ use super::Group;
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: Uuid,
+    pub org_id: Uuid,
     pub name: String,
     pub phone: Option<String>,
     pub role: Option<String>,
     pub email: String,
+    pub email_verified_at: Option<i64>,
+    pub pending_email: Option<String>,
     pub username: String,
     #[serde(skip)]
     pub password_hash: Vec<u8>,
     pub visible: bool,
     pub editable: bool,
     pub locked: bool,
+    pub disabled: bool,
+    pub mfa_enabled_at: Option<i64>,
+    pub registered_at: Option<i64>,
+    /// Free-form attributes checked by the policies, e.g. its department.
+    pub attributes: Json<Map<String, Value>>,
+    #[serde(skip)]
+    pub token_version: i32,
     pub created_at: i64,
     pub updated_at: i64,
     pub deleted_at: Option<i64>,
@@ -27,6 +38,19 @@ pub struct UserWithGroups {
     #[serde(flatten)]
     pub user: User,
     pub groups: Vec<Group>,
+    /// Groups the user only belongs to through the groups it is member of.
+    pub inherited_groups: Vec<Group>,
+    /// Next time a membership of the user starts or ends, tokens must not
+    /// outlive it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memberships_change_at: Option<i64>,
+}
+
+impl UserWithGroups {
+    /// Direct and inherited groups of the user.
+    pub fn all_groups(&self) -> impl Iterator<Item = &Group> + Clone {
+        self.groups.iter().chain(self.inherited_groups.iter())
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -42,7 +66,15 @@ pub struct UserCreateDto {
     pub locked: bool,
     #[serde(skip)]
     pub password_hash: Vec<u8>,
+    #[serde(default)]
+    pub attributes: Option<Map<String, Value>>,
     pub groups: Vec<Uuid>,
+    /// Creates the user with its address already verified.
+    #[serde(skip)]
+    pub email_verified: bool,
+    /// Marks the user as registered by itself.
+    #[serde(skip)]
+    pub registered: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,12 +87,33 @@ pub struct UserUpdateDto {
     pub visible: bool,
     pub editable: bool,
     pub locked: bool,
+    pub disabled: Option<bool>,
+    #[serde(default)]
+    pub attributes: Option<Map<String, Value>>,
     pub groups: Vec<Uuid>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RegisterDto {
+    pub name: String,
+    pub phone: Option<String>,
+    pub email: String,
+    pub username: String,
+    pub password: String,
+    pub invite_code: Option<String>,
+    /// Name of the organization to join, the system organization when
+    /// omitted.
+    pub organization: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ProfileDto {
     pub name: String,
     pub phone: Option<String>,
     pub role: Option<String>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct EmailDto {
+    pub email: String,
+}
@@ -0,0 +1,66 @@
+use sqlx::{query, query_as, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::model::User;
+
+#[derive(Clone)]
+pub struct EmailVerificationRepository {
+    db: Pool<sqlx::Postgres>,
+}
+
+impl EmailVerificationRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        EmailVerificationRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        token_hash: Vec<u8>,
+        expires_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        let sql = r#"insert into email_verifications
+            (user_id, email, token_hash, expires_at)
+        values
+            ($1, $2, $3, $4)"#;
+        query(sql)
+            .bind(user_id)
+            .bind(email)
+            .bind(token_hash)
+            .bind(expires_at)
+            .execute(self.db())
+            .await?;
+        Ok(())
+    }
+
+    /// Consumes a verification token and marks its address as the verified
+    /// email of the user, replacing the current one if it was a pending change.
+    pub async fn confirm(&self, token_hash: Vec<u8>) -> Result<User, sqlx::Error> {
+        let mut tx = self.db().begin().await?;
+        let sql = r#"update email_verifications set
+            used_at = extract(epoch from now())
+        where token_hash = $1 and used_at is null and expires_at > extract(epoch from now())
+        returning user_id, email"#;
+        let (user_id, email): (Uuid, String) =
+            query_as(sql).bind(token_hash).fetch_one(&mut *tx).await?;
+        let sql = r#"update users set
+            email = $2,
+            email_verified_at = extract(epoch from now()),
+            pending_email = null,
+            updated_at = extract(epoch from now())
+        where id = $1 and (email = $2 or pending_email = $2)
+        returning *"#;
+        let user = query_as(sql)
+            .bind(user_id)
+            .bind(email)
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(user)
+    }
+}
@@ -0,0 +1,103 @@
+use sqlx::{query_as, query_scalar, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::model::{Grant, GrantDto};
+
+#[derive(Clone)]
+pub struct GrantRepository {
+    db: Pool<Postgres>,
+}
+
+impl GrantRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        GrantRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    pub async fn find_by_resource(
+        &self,
+        resource_type: &str,
+        resource_id: Uuid,
+    ) -> Result<Vec<Grant>, sqlx::Error> {
+        let sql = r#"select * from grants
+        where resource_type = $1 and resource_id = $2
+        order by created_at"#;
+        query_as(sql)
+            .bind(resource_type)
+            .bind(resource_id)
+            .fetch_all(self.db())
+            .await
+    }
+
+    /// Permissions the user has on the resource, directly or through its
+    /// groups.
+    pub async fn permissions(
+        &self,
+        user_id: Uuid,
+        resource_type: &str,
+        resource_id: Uuid,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let sql = r#"with recursive user_groups(id) as (
+            select group_id from active_users_groups where user_id = $1
+            union
+            select gg.group_id from active_groups_groups gg
+                join user_groups ug on gg.member_id = ug.id
+        )
+        select permission from grants
+        where resource_type = $2 and resource_id = $3
+            and (user_id = $1 or group_id in (select id from user_groups))"#;
+        query_scalar(sql)
+            .bind(user_id)
+            .bind(resource_type)
+            .bind(resource_id)
+            .fetch_all(self.db())
+            .await
+    }
+
+    /// Grants the permission to a user or group of the organization, fails
+    /// with `RowNotFound` if it belongs to another one.
+    pub async fn create(
+        &self,
+        org_id: Uuid,
+        resource_type: &str,
+        resource_id: Uuid,
+        dto: GrantDto,
+    ) -> Result<Grant, sqlx::Error> {
+        let sql = r#"insert into grants
+            (user_id, group_id, permission, resource_type, resource_id)
+        select $1, $2, $3, $4, $5
+        where exists(select 1 from users where id = $1 and org_id = $6 and deleted_at is null)
+            or exists(select 1 from groups where id = $2 and org_id = $6 and deleted_at is null)
+        returning *"#;
+        query_as(sql)
+            .bind(dto.user_id)
+            .bind(dto.group_id)
+            .bind(dto.permission)
+            .bind(resource_type)
+            .bind(resource_id)
+            .bind(org_id)
+            .fetch_one(self.db())
+            .await
+    }
+
+    pub async fn delete(
+        &self,
+        id: Uuid,
+        resource_type: &str,
+        resource_id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let sql = r#"delete from grants
+        where id = $1 and resource_type = $2 and resource_id = $3
+        returning id"#;
+        query_scalar::<_, Uuid>(sql)
+            .bind(id)
+            .bind(resource_type)
+            .bind(resource_id)
+            .fetch_one(self.db())
+            .await?;
+        Ok(())
+    }
+}
@@ -1,7 +1,30 @@
-use crate::model::{Group, GroupDto};
-use sqlx::{query_as, query_scalar, types::Json, Pool};
+use crate::{
+    model::{Group, GroupDto, User},
+    security::token_version,
+};
+use sqlx::{query, query_as, query_scalar, PgConnection, Pool, Postgres};
 use uuid::Uuid;
 
+use super::{root_guard::RootGuard, SoftDelete};
+
+/// Invalidates the tokens of the members of a group, including the members of
+/// the groups it contains.
+const BUMP_MEMBERS_SQL: &str = r#"with recursive members(id) as (
+    select $1::uuid
+    union
+    select gg.member_id from groups_groups gg join members m on gg.group_id = m.id
+)
+update users set token_version = token_version + 1
+where id in (select user_id from users_groups where group_id in (select id from members))"#;
+
+/// Columns of a `Group` selected from `groups g`, with its permissions
+/// gathered from `groups_permissions`.
+pub(super) const GROUP_COLUMNS: &str = r#"g.*, coalesce(
+    (select jsonb_agg(gp.permission order by gp.permission)
+    from groups_permissions gp where gp.group_id = g.id),
+    '[]'
+) as permissions"#;
+
 #[derive(Clone)]
 pub struct GroupRepository {
     db: Pool<sqlx::Postgres>,
@@ -21,51 +44,340 @@ impl GroupRepository {
         query_scalar(sql).fetch_one(self.db()).await
     }
 
-    pub async fn find_all(&self) -> Result<Vec<Group>, sqlx::Error> {
-        let sql = "select * from groups";
-        query_as(sql).fetch_all(self.db()).await
+    /// Groups of the organization, deleted groups are left out unless
+    /// `include_deleted` is set.
+    pub async fn find_all(
+        &self,
+        org_id: Uuid,
+        include_deleted: bool,
+    ) -> Result<Vec<Group>, sqlx::Error> {
+        let sql = format!(
+            "select {} from groups g where org_id = $1 and ($2 or deleted_at is null)",
+            GROUP_COLUMNS
+        );
+        query_as(&sql)
+            .bind(org_id)
+            .bind(include_deleted)
+            .fetch_all(self.db())
+            .await
+    }
+
+    pub async fn find_by_id(&self, org_id: Uuid, id: Uuid) -> Result<Group, sqlx::Error> {
+        let sql = format!(
+            "select {} from groups g where id = $1 and org_id = $2 and deleted_at is null",
+            GROUP_COLUMNS
+        );
+        query_as(&sql)
+            .bind(id)
+            .bind(org_id)
+            .fetch_one(self.db())
+            .await
+    }
+
+    /// A deleted group of the organization, for `restore`.
+    pub async fn find_deleted(&self, org_id: Uuid, id: Uuid) -> Result<Group, sqlx::Error> {
+        let sql = format!(
+            "select {} from groups g where id = $1 and org_id = $2 and deleted_at is not null",
+            GROUP_COLUMNS
+        );
+        query_as(&sql)
+            .bind(id)
+            .bind(org_id)
+            .fetch_one(self.db())
+            .await
+    }
+
+    /// Groups directly containing the group, deleted or not.
+    pub async fn parent_ids(&self, id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+        let sql = "select group_id from groups_groups where member_id = $1";
+        query_scalar(sql).bind(id).fetch_all(self.db()).await
     }
 
-    pub async fn find_by_id(&self, id: Uuid) -> Result<Group, sqlx::Error> {
-        let sql = "select * from groups where id = $1";
-        query_as(sql).bind(id).fetch_one(self.db()).await
+    pub async fn find_by_ids(&self, ids: &[Uuid]) -> Result<Vec<Group>, sqlx::Error> {
+        let sql = format!(
+            "select {} from groups g where id = any($1) and deleted_at is null",
+            GROUP_COLUMNS
+        );
+        query_as(&sql).bind(ids).fetch_all(self.db()).await
+    }
+
+    /// Returns the groups and every group containing them, directly or not.
+    pub async fn find_with_ancestors(&self, ids: &[Uuid]) -> Result<Vec<Group>, sqlx::Error> {
+        let sql = format!(
+            r#"with recursive ancestors(id) as (
+                select unnest($1::uuid[])
+                union
+                select gg.group_id from active_groups_groups gg
+                    join ancestors a on gg.member_id = a.id
+            )
+            select {} from groups g join ancestors a on g.id = a.id
+            where g.deleted_at is null"#,
+            GROUP_COLUMNS
+        );
+        query_as(&sql).bind(ids).fetch_all(self.db()).await
+    }
+
+    pub async fn find_by_name(&self, org_id: Uuid, name: &str) -> Result<Group, sqlx::Error> {
+        let sql = format!(
+            "select {} from groups g where name = $1 and org_id = $2 and deleted_at is null",
+            GROUP_COLUMNS
+        );
+        query_as(&sql)
+            .bind(name)
+            .bind(org_id)
+            .fetch_one(self.db())
+            .await
     }
 
-    pub async fn create(&self, dto: GroupDto) -> Result<Group, sqlx::Error> {
+    async fn set_permissions(
+        conn: &mut PgConnection,
+        id: Uuid,
+        permissions: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        let sql = "delete from groups_permissions where group_id = $1";
+        query(sql).bind(id).execute(&mut *conn).await?;
+        let sql = r#"insert into groups_permissions (group_id, permission)
+            select distinct $1, unnest($2::varchar[])"#;
+        query(sql).bind(id).bind(permissions).execute(conn).await?;
+        Ok(())
+    }
+
+    pub async fn create(&self, org_id: Uuid, dto: GroupDto) -> Result<Group, sqlx::Error> {
+        let mut tx = self.db().begin().await?;
         let sql = r#"insert into groups
-            (name, description, permissions, visible, editable, locked)
+            (name, description, visible, editable, locked, require_mfa,
+            allow_passwordless, idle_timeout, max_sessions, org_id)
         values
-            ($1, $2, $3, $4, $5, $6)
-        returning *"#;
-        query_as(&sql)
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        returning id"#;
+        let id: Uuid = query_scalar(sql)
             .bind(dto.name)
             .bind(dto.description)
-            .bind(Json(dto.permissions))
             .bind(dto.visible.unwrap_or(true))
             .bind(dto.editable.unwrap_or(true))
             .bind(dto.locked.unwrap_or(false))
-            .fetch_one(self.db())
-            .await
+            .bind(dto.require_mfa.unwrap_or(false))
+            .bind(dto.allow_passwordless.unwrap_or(false))
+            .bind(dto.idle_timeout)
+            .bind(dto.max_sessions)
+            .bind(org_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        Self::set_permissions(&mut tx, id, dto.permissions).await?;
+        tx.commit().await?;
+        self.find_by_id(org_id, id).await
     }
 
-    pub async fn update(&self, id: Uuid, dto: GroupDto) -> Result<Group, sqlx::Error> {
+    /// Updates a group, its members must get new tokens when its permissions
+    /// change. Returns nothing and changes nothing if it takes `root` away
+    /// from the last active root user.
+    pub async fn update(
+        &self,
+        org_id: Uuid,
+        id: Uuid,
+        dto: GroupDto,
+    ) -> Result<Option<Group>, sqlx::Error> {
+        let before = self.find_by_id(org_id, id).await?;
+        let mut guard = RootGuard::begin(self.db(), org_id).await?;
         let sql = r#"update groups set
             name = $2,
             description = $3,
-            permissions = $4,
-            visible = $5,
-            editable = $6,
-            locked = $7
-        where id = $1 returning *"#;
-        query_as(&sql)
+            visible = $4,
+            editable = $5,
+            locked = $6,
+            require_mfa = $7,
+            allow_passwordless = $8,
+            idle_timeout = $9,
+            max_sessions = $10,
+            updated_at = extract(epoch from now())
+        where id = $1"#;
+        query(sql)
             .bind(id)
             .bind(dto.name)
             .bind(dto.description)
-            .bind(Json(dto.permissions))
             .bind(dto.visible.unwrap_or(true))
             .bind(dto.editable.unwrap_or(true))
             .bind(dto.locked.unwrap_or(false))
+            .bind(dto.require_mfa.unwrap_or(false))
+            .bind(dto.allow_passwordless.unwrap_or(false))
+            .bind(dto.idle_timeout)
+            .bind(dto.max_sessions)
+            .execute(guard.conn())
+            .await?;
+        Self::set_permissions(guard.conn(), id, dto.permissions).await?;
+        if !guard.commit().await? {
+            return Ok(None);
+        }
+        let group = self.find_by_id(org_id, id).await?;
+        if group.permissions.0 != before.permissions.0 {
+            query(BUMP_MEMBERS_SQL).bind(id).execute(self.db()).await?;
+            token_version::clear();
+        }
+        Ok(Some(group))
+    }
+
+    /// Soft-deletes a group, its members must get new tokens. Its members,
+    /// owners and grants are kept for `restore`. Returns `false` and changes
+    /// nothing if it takes `root` away from the last active root user.
+    pub async fn delete(&self, org_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut guard = RootGuard::begin(self.db(), org_id).await?;
+        self.soft_delete(guard.conn(), org_id, id).await?;
+        query(BUMP_MEMBERS_SQL)
+            .bind(id)
+            .execute(guard.conn())
+            .await?;
+        if !guard.commit().await? {
+            return Ok(false);
+        }
+        token_version::clear();
+        Ok(true)
+    }
+
+    /// Restores a deleted group, its members get its permissions back with
+    /// new tokens.
+    pub async fn restore(&self, org_id: Uuid, id: Uuid) -> Result<(), sqlx::Error> {
+        SoftDelete::restore(self, org_id, id).await?;
+        query(BUMP_MEMBERS_SQL).bind(id).execute(self.db()).await?;
+        token_version::clear();
+        Ok(())
+    }
+
+    /// Groups that are direct members of the group.
+    pub async fn member_groups(&self, id: Uuid) -> Result<Vec<Group>, sqlx::Error> {
+        let sql = format!(
+            r#"select {} from groups g
+                join groups_groups gg on g.id = gg.member_id
+            where gg.group_id = $1 and g.deleted_at is null"#,
+            GROUP_COLUMNS
+        );
+        query_as(&sql).bind(id).fetch_all(self.db()).await
+    }
+
+    /// Returns true if `member_id` is `id` or contains it, directly or not,
+    /// in which case adding it to `id` would create a cycle.
+    async fn contains(
+        conn: &mut PgConnection,
+        member_id: Uuid,
+        id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = r#"with recursive members(id) as (
+            select $1::uuid
+            union
+            select gg.member_id from groups_groups gg join members m on gg.group_id = m.id
+        )
+        select exists(select 1 from members where id = $2)"#;
+        query_scalar(sql)
+            .bind(member_id)
+            .bind(id)
+            .fetch_one(conn)
+            .await
+    }
+
+    /// Makes the members of `member_id` members of `id` as well. Returns
+    /// `false` and changes nothing if `member_id` contains `id`, the nesting
+    /// is locked meanwhile so concurrent additions can not create a cycle.
+    pub async fn add_member_group(&self, id: Uuid, member_id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut tx = self.db().begin().await?;
+        query("lock table groups_groups in share row exclusive mode")
+            .execute(&mut *tx)
+            .await?;
+        if Self::contains(&mut tx, member_id, id).await? {
+            return Ok(false);
+        }
+        let sql = r#"insert into groups_groups (group_id, member_id) values ($1, $2)
+        on conflict do nothing"#;
+        query(sql)
+            .bind(id)
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+        query(BUMP_MEMBERS_SQL)
+            .bind(member_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        token_version::clear();
+        Ok(true)
+    }
+
+    /// Takes the members of `member_id` out of `id`. Returns `false` and
+    /// changes nothing if it takes `root` away from the last active root user.
+    pub async fn remove_member_group(
+        &self,
+        org_id: Uuid,
+        id: Uuid,
+        member_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let mut guard = RootGuard::begin(self.db(), org_id).await?;
+        let sql =
+            "delete from groups_groups where group_id = $1 and member_id = $2 returning group_id";
+        query_scalar::<_, Uuid>(sql)
+            .bind(id)
+            .bind(member_id)
+            .fetch_one(guard.conn())
+            .await?;
+        query(BUMP_MEMBERS_SQL)
+            .bind(member_id)
+            .execute(guard.conn())
+            .await?;
+        if !guard.commit().await? {
+            return Ok(false);
+        }
+        token_version::clear();
+        Ok(true)
+    }
+
+    /// Direct members of the group whose membership is valid.
+    pub async fn members(&self, id: Uuid) -> Result<Vec<User>, sqlx::Error> {
+        let sql = r#"select u.* from users u
+            join active_users_groups ug on u.id = ug.user_id
+        where ug.group_id = $1
+        order by u.name"#;
+        query_as(sql).bind(id).fetch_all(self.db()).await
+    }
+
+    pub async fn owners(&self, id: Uuid) -> Result<Vec<User>, sqlx::Error> {
+        let sql = r#"select u.* from users u
+            join groups_owners go on u.id = go.user_id
+        where go.group_id = $1 and u.deleted_at is null
+        order by u.name"#;
+        query_as(sql).bind(id).fetch_all(self.db()).await
+    }
+
+    pub async fn is_owner(&self, id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let sql = "select exists(select 1 from groups_owners where group_id = $1 and user_id = $2)";
+        query_scalar(sql)
+            .bind(id)
+            .bind(user_id)
             .fetch_one(self.db())
             .await
     }
+
+    pub async fn add_owner(&self, id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let sql = r#"insert into groups_owners (group_id, user_id) values ($1, $2)
+        on conflict do nothing"#;
+        query(sql).bind(id).bind(user_id).execute(self.db()).await?;
+        Ok(())
+    }
+
+    pub async fn remove_owner(&self, id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let sql =
+            "delete from groups_owners where group_id = $1 and user_id = $2 returning user_id";
+        query_scalar::<_, Uuid>(sql)
+            .bind(id)
+            .bind(user_id)
+            .fetch_one(self.db())
+            .await?;
+        Ok(())
+    }
+}
+
+impl SoftDelete for GroupRepository {
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    fn table(&self) -> &str {
+        "groups"
+    }
 }
@@ -0,0 +1,128 @@
+use sqlx::{query, query_scalar, Pool, Postgres};
+use uuid::Uuid;
+
+/// Failed attempts after which emailed login codes stop being accepted, they
+/// are counted across the codes of the user issued during
+/// `CODE_ATTEMPTS_WINDOW` so requesting a new code does not reset them.
+const MAX_CODE_ATTEMPTS: i64 = 5;
+
+/// Seconds during which failed code attempts are counted.
+const CODE_ATTEMPTS_WINDOW: i64 = 60 * 60;
+
+/// Failed code attempts of the user during the window.
+const CODE_ATTEMPTS_SQL: &str = r#"select coalesce(sum(attempts), 0) from login_tokens
+where method = 'code' and user_id = $1 and created_at > extract(epoch from now()) - $2"#;
+
+#[derive(Clone)]
+pub struct LoginTokenRepository {
+    db: Pool<sqlx::Postgres>,
+}
+
+impl LoginTokenRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        LoginTokenRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    /// Returns true if the user spent its failed code attempts, no new code is
+    /// issued until the window passes.
+    pub async fn code_attempts_spent(&self, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let attempts: i64 = query_scalar(CODE_ATTEMPTS_SQL)
+            .bind(user_id)
+            .bind(CODE_ATTEMPTS_WINDOW)
+            .fetch_one(self.db())
+            .await?;
+        Ok(attempts >= MAX_CODE_ATTEMPTS)
+    }
+
+    /// Stores a new login token, the unused ones of the same method expire
+    /// but are kept during the window for their failed attempts.
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        method: &str,
+        token_hash: Vec<u8>,
+        expires_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.db().begin().await?;
+        let sql = r#"delete from login_tokens
+        where user_id = $1 and method = $2 and created_at <= extract(epoch from now()) - $3"#;
+        query(sql)
+            .bind(user_id)
+            .bind(method)
+            .bind(CODE_ATTEMPTS_WINDOW)
+            .execute(&mut *tx)
+            .await?;
+        let sql = r#"update login_tokens set
+            expires_at = least(expires_at, extract(epoch from now()))
+        where user_id = $1 and method = $2 and used_at is null"#;
+        query(sql)
+            .bind(user_id)
+            .bind(method)
+            .execute(&mut *tx)
+            .await?;
+        let sql = r#"insert into login_tokens
+            (user_id, method, token_hash, expires_at)
+        values
+            ($1, $2, $3, $4)"#;
+        query(sql)
+            .bind(user_id)
+            .bind(method)
+            .bind(token_hash)
+            .bind(expires_at)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Consumes a login link and returns the id of its user.
+    pub async fn consume_link(&self, token_hash: Vec<u8>) -> Result<Uuid, sqlx::Error> {
+        let sql = r#"update login_tokens set
+            used_at = extract(epoch from now())
+        where method = 'link' and token_hash = $1 and used_at is null
+            and expires_at > extract(epoch from now())
+        returning user_id"#;
+        query_scalar(sql)
+            .bind(token_hash)
+            .fetch_one(self.db())
+            .await
+    }
+
+    /// Consumes a login code of the user, returns `false` and counts a failed
+    /// attempt if the code is incorrect.
+    pub async fn consume_code(
+        &self,
+        user_id: Uuid,
+        token_hash: Vec<u8>,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            r#"update login_tokens set
+                used_at = extract(epoch from now())
+            where method = 'code' and user_id = $1 and token_hash = $3 and used_at is null
+                and expires_at > extract(epoch from now())
+                and ({}) < $4
+            returning id"#,
+            CODE_ATTEMPTS_SQL
+        );
+        let id: Option<Uuid> = query_scalar(&sql)
+            .bind(user_id)
+            .bind(CODE_ATTEMPTS_WINDOW)
+            .bind(token_hash)
+            .bind(MAX_CODE_ATTEMPTS)
+            .fetch_optional(self.db())
+            .await?;
+        if id.is_some() {
+            return Ok(true);
+        }
+
+        let sql = r#"update login_tokens set
+            attempts = attempts + 1
+        where method = 'code' and user_id = $1 and used_at is null
+            and expires_at > extract(epoch from now())"#;
+        query(sql).bind(user_id).execute(self.db()).await?;
+        Ok(false)
+    }
+}
@@ -0,0 +1,138 @@
+use sqlx::{query, query_as, query_scalar, Pool, Postgres};
+use uuid::Uuid;
+
+use super::user_repository::ADD_TO_GROUP_SQL;
+use crate::{
+    model::{
+        MembershipRequest, MembershipRequestDto, REQUEST_APPROVED, REQUEST_PENDING,
+        REQUEST_REJECTED,
+    },
+    security::token_version,
+};
+
+/// Decides a request, only pending requests can be decided.
+const DECIDE_SQL: &str = r#"update membership_requests set
+    status = $2,
+    decided_by = $3,
+    decided_at = extract(epoch from now()),
+    updated_at = extract(epoch from now())
+where id = $1 and status = $4
+returning *"#;
+
+#[derive(Clone)]
+pub struct MembershipRequestRepository {
+    db: Pool<Postgres>,
+}
+
+impl MembershipRequestRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        MembershipRequestRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    pub async fn find(&self, id: Uuid, group_id: Uuid) -> Result<MembershipRequest, sqlx::Error> {
+        let sql = "select * from membership_requests where id = $1 and group_id = $2";
+        query_as(sql)
+            .bind(id)
+            .bind(group_id)
+            .fetch_one(self.db())
+            .await
+    }
+
+    pub async fn find_by_group(
+        &self,
+        group_id: Uuid,
+    ) -> Result<Vec<MembershipRequest>, sqlx::Error> {
+        let sql = "select * from membership_requests where group_id = $1 order by created_at desc";
+        query_as(sql).bind(group_id).fetch_all(self.db()).await
+    }
+
+    pub async fn find_by_user(&self, user_id: Uuid) -> Result<Vec<MembershipRequest>, sqlx::Error> {
+        let sql = "select * from membership_requests where user_id = $1 order by created_at desc";
+        query_as(sql).bind(user_id).fetch_all(self.db()).await
+    }
+
+    pub async fn has_pending(&self, user_id: Uuid, group_id: Uuid) -> Result<bool, sqlx::Error> {
+        let sql = r#"select exists(
+            select 1 from membership_requests
+            where user_id = $1 and group_id = $2 and status = $3
+        )"#;
+        query_scalar(sql)
+            .bind(user_id)
+            .bind(group_id)
+            .bind(REQUEST_PENDING)
+            .fetch_one(self.db())
+            .await
+    }
+
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        group_id: Uuid,
+        dto: MembershipRequestDto,
+    ) -> Result<MembershipRequest, sqlx::Error> {
+        let sql = r#"insert into membership_requests
+            (user_id, group_id, reason, valid_from, valid_until)
+        values
+            ($1, $2, $3, $4, $5)
+        returning *"#;
+        query_as(sql)
+            .bind(user_id)
+            .bind(group_id)
+            .bind(dto.reason)
+            .bind(dto.valid_from)
+            .bind(dto.valid_until)
+            .fetch_one(self.db())
+            .await
+    }
+
+    /// Approves a pending request and adds the user to the group for the
+    /// requested time. Returns `None` and leaves the request pending if the
+    /// user has a membership valid beyond that time.
+    pub async fn approve(
+        &self,
+        id: Uuid,
+        decided_by: Uuid,
+    ) -> Result<Option<MembershipRequest>, sqlx::Error> {
+        let mut tx = self.db().begin().await?;
+        let request: MembershipRequest = query_as(DECIDE_SQL)
+            .bind(id)
+            .bind(REQUEST_APPROVED)
+            .bind(decided_by)
+            .bind(REQUEST_PENDING)
+            .fetch_one(&mut *tx)
+            .await?;
+        let added: Option<Uuid> = query_scalar(ADD_TO_GROUP_SQL)
+            .bind(request.user_id)
+            .bind(request.group_id)
+            .bind(request.valid_from)
+            .bind(request.valid_until)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if added.is_none() {
+            return Ok(None);
+        }
+        let sql = "update users set token_version = token_version + 1 where id = $1";
+        query(sql).bind(request.user_id).execute(&mut *tx).await?;
+        tx.commit().await?;
+        token_version::invalidate(request.user_id);
+        Ok(Some(request))
+    }
+
+    pub async fn reject(
+        &self,
+        id: Uuid,
+        decided_by: Uuid,
+    ) -> Result<MembershipRequest, sqlx::Error> {
+        query_as(DECIDE_SQL)
+            .bind(id)
+            .bind(REQUEST_REJECTED)
+            .bind(decided_by)
+            .bind(REQUEST_PENDING)
+            .fetch_one(self.db())
+            .await
+    }
+}
@@ -0,0 +1,69 @@
+use sqlx::{query, query_scalar, Pool, Postgres};
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct MfaRepository {
+    db: Pool<sqlx::Postgres>,
+}
+
+impl MfaRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        MfaRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    pub async fn enable(&self, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let sql = r#"update users set
+            mfa_enabled_at = extract(epoch from now()),
+            updated_at = extract(epoch from now())
+        where id = $1"#;
+        query(sql).bind(user_id).execute(self.db()).await?;
+        Ok(())
+    }
+
+    /// Replaces every unused recovery code of the user with the given hashes.
+    /// Used codes are kept as a record of past logins.
+    pub async fn replace_recovery_codes(
+        &self,
+        user_id: Uuid,
+        hashes: Vec<Vec<u8>>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.db().begin().await?;
+        let sql = "delete from recovery_codes where user_id = $1 and used_at is null";
+        query(sql).bind(user_id).execute(&mut *tx).await?;
+        let sql = "insert into recovery_codes (user_id, code_hash) select $1, unnest($2)";
+        query(sql)
+            .bind(user_id)
+            .bind(hashes)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Marks a recovery code as used, returns `false` if the code does not
+    /// exist or was already used.
+    pub async fn use_recovery_code(
+        &self,
+        user_id: Uuid,
+        code_hash: Vec<u8>,
+    ) -> Result<bool, sqlx::Error> {
+        let sql = r#"update recovery_codes set
+            used_at = extract(epoch from now())
+        where user_id = $1 and code_hash = $2 and used_at is null
+        returning id"#;
+        let id: Option<Uuid> = query_scalar(sql)
+            .bind(user_id)
+            .bind(code_hash)
+            .fetch_optional(self.db())
+            .await?;
+        Ok(id.is_some())
+    }
+
+    pub async fn remaining_recovery_codes(&self, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        let sql = "select count(*) from recovery_codes where user_id = $1 and used_at is null";
+        query_scalar(sql).bind(user_id).fetch_one(self.db()).await
+    }
+}
@@ -1,5 +1,26 @@
+mod email_verification_repository;
+mod grant_repository;
 mod group_repository;
+mod login_token_repository;
+mod membership_request_repository;
+mod mfa_repository;
+mod organization_repository;
+mod permission_repository;
+mod policy_repository;
+mod root_guard;
+mod session_repository;
+mod soft_delete;
 mod user_repository;
 
+pub use email_verification_repository::EmailVerificationRepository;
+pub use grant_repository::GrantRepository;
 pub use group_repository::GroupRepository;
+pub use login_token_repository::LoginTokenRepository;
+pub use membership_request_repository::MembershipRequestRepository;
+pub use mfa_repository::MfaRepository;
+pub use organization_repository::OrganizationRepository;
+pub use permission_repository::PermissionRepository;
+pub use policy_repository::PolicyRepository;
+pub use session_repository::SessionRepository;
+pub(crate) use soft_delete::SoftDelete;
 pub use user_repository::UserRepository;
@@ -0,0 +1,83 @@
+use sqlx::{query_as, query_scalar, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::model::{Organization, OrganizationDto, SYSTEM_ORGANIZATION};
+
+#[derive(Clone)]
+pub struct OrganizationRepository {
+    db: Pool<Postgres>,
+}
+
+impl OrganizationRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        OrganizationRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<Organization>, sqlx::Error> {
+        let sql = "select * from organizations order by name";
+        query_as(sql).fetch_all(self.db()).await
+    }
+
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Organization, sqlx::Error> {
+        let sql = "select * from organizations where id = $1";
+        query_as(sql).bind(id).fetch_one(self.db()).await
+    }
+
+    /// Returns the id of the organization named `name`, the system
+    /// organization when no name is given.
+    pub async fn resolve(&self, name: Option<&str>) -> Result<Uuid, sqlx::Error> {
+        let Some(name) = name else {
+            return Ok(SYSTEM_ORGANIZATION);
+        };
+        let sql = "select id from organizations where name = $1";
+        query_scalar(sql).bind(name).fetch_one(self.db()).await
+    }
+
+    /// Returns true if users may register themselves into the organization.
+    pub async fn allows_registration(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let sql = "select allow_registration from organizations where id = $1";
+        query_scalar(sql).bind(id).fetch_one(self.db()).await
+    }
+
+    pub async fn exists(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let sql = "select exists(select 1 from organizations where id = $1)";
+        query_scalar(sql).bind(id).fetch_one(self.db()).await
+    }
+
+    pub async fn create(&self, dto: OrganizationDto) -> Result<Organization, sqlx::Error> {
+        let sql = r#"insert into organizations (name, description, allow_registration)
+        values ($1, $2, coalesce($3, false))
+        returning *"#;
+        query_as(sql)
+            .bind(dto.name)
+            .bind(dto.description)
+            .bind(dto.allow_registration)
+            .fetch_one(self.db())
+            .await
+    }
+
+    pub async fn update(
+        &self,
+        id: Uuid,
+        dto: OrganizationDto,
+    ) -> Result<Organization, sqlx::Error> {
+        let sql = r#"update organizations set
+            name = $2,
+            description = $3,
+            allow_registration = coalesce($4, allow_registration),
+            updated_at = extract(epoch from now())
+        where id = $1
+        returning *"#;
+        query_as(sql)
+            .bind(id)
+            .bind(dto.name)
+            .bind(dto.description)
+            .bind(dto.allow_registration)
+            .fetch_one(self.db())
+            .await
+    }
+}
@@ -0,0 +1,72 @@
+use sqlx::{query_as, query_scalar, Pool, Postgres};
+
+use crate::model::{Permission, PermissionDto};
+
+#[derive(Clone)]
+pub struct PermissionRepository {
+    db: Pool<Postgres>,
+}
+
+impl PermissionRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        PermissionRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<Permission>, sqlx::Error> {
+        let sql = "select * from permissions order by service, name";
+        query_as(sql).fetch_all(self.db()).await
+    }
+
+    pub async fn names(&self) -> Result<Vec<String>, sqlx::Error> {
+        let sql = "select name from permissions";
+        query_scalar(sql).fetch_all(self.db()).await
+    }
+
+    /// Names among `names` registered by another service, permissions without
+    /// a service are not taken.
+    pub async fn owned_by_others(
+        &self,
+        service: &str,
+        names: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let sql =
+            "select name from permissions where name = any($1) and service <> $2 order by name";
+        query_scalar(sql)
+            .bind(names)
+            .bind(service)
+            .fetch_all(self.db())
+            .await
+    }
+
+    /// Adds or updates the permissions of a service, permissions it no longer
+    /// registers are kept since groups may still grant them. Permissions
+    /// without a service are claimed by the first one registering them.
+    pub async fn register(
+        &self,
+        service: &str,
+        permissions: Vec<PermissionDto>,
+    ) -> Result<Vec<Permission>, sqlx::Error> {
+        let (names, descriptions): (Vec<String>, Vec<Option<String>>) = permissions
+            .into_iter()
+            .map(|permission| (permission.name, permission.description))
+            .unzip();
+        let sql = r#"insert into permissions (name, description, service)
+            select name, description, $3 from unnest($1::varchar[], $2::varchar[]) as p(name, description)
+        on conflict (name) do update set
+            description = excluded.description,
+            service = excluded.service,
+            updated_at = extract(epoch from now())
+        where permissions.service is null or permissions.service = excluded.service
+        returning *"#;
+        query_as(sql)
+            .bind(names)
+            .bind(descriptions)
+            .bind(service)
+            .fetch_all(self.db())
+            .await
+    }
+}
@@ -0,0 +1,24 @@
+use sqlx::{query_scalar, types::Json, Pool, Postgres};
+
+use crate::security::policy::Rule;
+
+#[derive(Clone)]
+pub struct PolicyRepository {
+    db: Pool<Postgres>,
+}
+
+impl PolicyRepository {
+    pub fn new(db: Pool<Postgres>) -> Self {
+        PolicyRepository { db }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    pub async fn find_enabled(&self) -> Result<Vec<Rule>, sqlx::Error> {
+        let sql = "select rule from policies where enabled order by created_at";
+        let rules: Vec<Json<Rule>> = query_scalar(sql).fetch_all(self.db()).await?;
+        Ok(rules.into_iter().map(|rule| rule.0).collect())
+    }
+}
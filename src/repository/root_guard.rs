@@ -0,0 +1,66 @@
+use sqlx::{query, query_scalar, PgConnection, Pool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// Transaction of a change that may take the `root` permission away from the
+/// users of an organization. The groups granting it are locked first, so such
+/// changes run one after another, and the change is rolled back if it leaves
+/// no active root user where there was one.
+pub(super) struct RootGuard {
+    tx: Transaction<'static, Postgres>,
+    org_id: Uuid,
+    had_root: bool,
+}
+
+impl RootGuard {
+    pub(super) async fn begin(db: &Pool<Postgres>, org_id: Uuid) -> Result<Self, sqlx::Error> {
+        let mut tx = db.begin().await?;
+        let sql = r#"select g.id from groups g
+            join groups_permissions gp on gp.group_id = g.id
+        where gp.permission = 'root' and g.org_id = $1
+        order by g.id
+        for no key update of g"#;
+        query(sql).bind(org_id).execute(&mut *tx).await?;
+        let had_root = has_active_root(&mut tx, org_id).await?;
+        Ok(RootGuard {
+            tx,
+            org_id,
+            had_root,
+        })
+    }
+
+    pub(super) fn conn(&mut self) -> &mut PgConnection {
+        &mut self.tx
+    }
+
+    /// Commits the change, or rolls it back and returns `false` if it left the
+    /// organization without an active root user.
+    pub(super) async fn commit(mut self) -> Result<bool, sqlx::Error> {
+        if self.had_root && !has_active_root(&mut self.tx, self.org_id).await? {
+            self.tx.rollback().await?;
+            return Ok(false);
+        }
+        self.tx.commit().await?;
+        Ok(true)
+    }
+}
+
+/// Returns true if an active user of the organization has the `root`
+/// permission. Memberships that expire do not count, they would leave the
+/// organization without root later.
+async fn has_active_root(conn: &mut PgConnection, org_id: Uuid) -> Result<bool, sqlx::Error> {
+    let sql = r#"with recursive memberships(user_id, group_id) as (
+        select user_id, group_id from active_users_groups where valid_until is null
+        union
+        select m.user_id, gg.group_id from active_groups_groups gg
+            join memberships m on gg.member_id = m.group_id
+    )
+    select exists(select 1 from users u
+        join memberships m on u.id = m.user_id
+        join groups g on g.id = m.group_id
+        join groups_permissions gp on gp.group_id = g.id
+    where gp.permission = 'root'
+        and g.deleted_at is null
+        and not u.locked and not u.disabled and u.deleted_at is null
+        and u.org_id = $1)"#;
+    query_scalar(sql).bind(org_id).fetch_one(conn).await
+}
@@ -0,0 +1,153 @@
+use sqlx::{query, query_as, query_scalar, Pool, Postgres};
+use uuid::Uuid;
+
+use crate::{
+    model::{Session, SessionPolicy},
+    security::ClientInfo,
+};
+
+/// Seconds between two updates of `last_seen_at` of the same session, less
+/// for sessions with a short idle timeout.
+const TOUCH_INTERVAL: i64 = 60;
+
+#[derive(Clone)]
+pub struct SessionRepository {
+    db: Pool<sqlx::Postgres>,
+    /// Sessions not seen for this many seconds are no longer active, it
+    /// matches the lifetime of the access tokens.
+    max_age: i64,
+}
+
+impl SessionRepository {
+    pub fn new(db: Pool<Postgres>, max_age: i64) -> Self {
+        SessionRepository { db, max_age }
+    }
+
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    /// Records a new session following the policy of the user's groups, the
+    /// oldest sessions over the limit are revoked.
+    pub async fn create(
+        &self,
+        user_id: Uuid,
+        client: &ClientInfo,
+        policy: &SessionPolicy,
+    ) -> Result<Session, sqlx::Error> {
+        let sql = r#"insert into sessions
+            (user_id, device, ip, user_agent, idle_timeout)
+        values
+            ($1, $2, $3, $4, $5)
+        returning *"#;
+        let session: Session = query_as(sql)
+            .bind(user_id)
+            .bind(&client.device)
+            .bind(&client.ip)
+            .bind(&client.user_agent)
+            .bind(policy.idle_timeout)
+            .fetch_one(self.db())
+            .await?;
+        self.evict(user_id, policy).await?;
+        Ok(session)
+    }
+
+    /// Applies the current policy of the user's groups to one of its
+    /// sessions, used when the token of the session is refreshed.
+    pub async fn apply_policy(
+        &self,
+        id: Uuid,
+        user_id: Uuid,
+        policy: &SessionPolicy,
+    ) -> Result<(), sqlx::Error> {
+        let sql = "update sessions set idle_timeout = $3 where id = $1 and user_id = $2";
+        query(sql)
+            .bind(id)
+            .bind(user_id)
+            .bind(policy.idle_timeout)
+            .execute(self.db())
+            .await?;
+        self.evict(user_id, policy).await
+    }
+
+    /// Revokes the oldest active sessions of the user over `max_sessions`.
+    async fn evict(&self, user_id: Uuid, policy: &SessionPolicy) -> Result<(), sqlx::Error> {
+        let Some(max_sessions) = policy.max_sessions else {
+            return Ok(());
+        };
+        let sql = r#"update sessions set
+            revoked_at = extract(epoch from now())
+        where id in (
+            select id from sessions
+            where user_id = $1 and revoked_at is null
+                and last_seen_at > extract(epoch from now()) - $2
+            order by created_at desc
+            offset $3
+        )"#;
+        query(sql)
+            .bind(user_id)
+            .bind(self.max_age)
+            .bind(i64::from(max_sessions.max(1)))
+            .execute(self.db())
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the active sessions of the user, newest first.
+    pub async fn find_active(&self, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+        let sql = r#"select * from sessions
+        where user_id = $1 and revoked_at is null
+            and last_seen_at > extract(epoch from now()) - $2
+        order by created_at desc"#;
+        query_as(sql)
+            .bind(user_id)
+            .bind(self.max_age)
+            .fetch_all(self.db())
+            .await
+    }
+
+    /// Records activity on a session, returns `false` if the session does
+    /// not belong to the user, was revoked or expired after being idle.
+    pub async fn touch(&self, id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let sql = r#"select last_seen_at, idle_timeout from sessions
+        where id = $1 and user_id = $2 and revoked_at is null"#;
+        let session: Option<(i64, Option<i32>)> = query_as(sql)
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(self.db())
+            .await?;
+        let Some((last_seen_at, idle_timeout)) = session else {
+            return Ok(false);
+        };
+        let now = chrono::Utc::now().timestamp();
+        if let Some(idle_timeout) = idle_timeout {
+            if last_seen_at < now - i64::from(idle_timeout) * 60 {
+                self.revoke(id, user_id).await?;
+                return Ok(false);
+            }
+        }
+        // a session in use must not expire because of the throttling
+        let interval = idle_timeout.map_or(TOUCH_INTERVAL, |idle_timeout| {
+            TOUCH_INTERVAL.min(i64::from(idle_timeout) * 60 / 2)
+        });
+        if last_seen_at < now - interval {
+            let sql = "update sessions set last_seen_at = extract(epoch from now()) where id = $1";
+            query(sql).bind(id).execute(self.db()).await?;
+        }
+        Ok(true)
+    }
+
+    /// Revokes a session of the user, the tokens issued for it stop working.
+    pub async fn revoke(&self, id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
+        let sql = r#"update sessions set
+            revoked_at = extract(epoch from now())
+        where id = $1 and user_id = $2 and revoked_at is null
+        returning id"#;
+        let _: Uuid = query_scalar(sql)
+            .bind(id)
+            .bind(user_id)
+            .fetch_one(self.db())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use sqlx::{query_scalar, PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+/// Soft deletion of the rows of a table with `deleted_at` and `org_id`, the
+/// queries of the repositories leave deleted rows out until they are
+/// restored.
+pub trait SoftDelete {
+    fn db(&self) -> &Pool<Postgres>;
+    fn table(&self) -> &str;
+
+    /// Marks a row of the organization as deleted, fails with `RowNotFound`
+    /// if there is none or it is already deleted. Runs on the transaction of
+    /// the caller, deleting may have to be undone.
+    async fn soft_delete(
+        &self,
+        conn: &mut PgConnection,
+        org_id: Uuid,
+        id: Uuid,
+    ) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"update {} set deleted_at = extract(epoch from now())
+            where id = $1 and org_id = $2 and deleted_at is null
+            returning id"#,
+            self.table()
+        );
+        query_scalar::<_, Uuid>(&sql)
+            .bind(id)
+            .bind(org_id)
+            .fetch_one(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Undoes `soft_delete`, fails with `RowNotFound` if the row is not
+    /// deleted.
+    async fn restore(&self, org_id: Uuid, id: Uuid) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            r#"update {} set deleted_at = null, updated_at = extract(epoch from now())
+            where id = $1 and org_id = $2 and deleted_at is not null
+            returning id"#,
+            self.table()
+        );
+        query_scalar::<_, Uuid>(&sql)
+            .bind(id)
+            .bind(org_id)
+            .fetch_one(self.db())
+            .await?;
+        Ok(())
+    }
+}
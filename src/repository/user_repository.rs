@@ -1,10 +1,31 @@
-use sqlx::{query, query_as, query_scalar, Pool, Postgres};
+use sqlx::{query, query_as, query_scalar, types::Json, PgConnection, PgExecutor, Pool, Postgres};
 use uuid::Uuid;
 
-use crate::model::{
-    Group, PasswordDto, ProfileDto, User, UserCreateDto, UserUpdateDto, UserWithGroups,
+use super::{group_repository::GROUP_COLUMNS, root_guard::RootGuard, SoftDelete};
+use crate::{
+    model::{Group, PasswordDto, ProfileDto, User, UserCreateDto, UserUpdateDto, UserWithGroups},
+    security::token_version,
 };
 
+/// Adds a user to a group between `$3` and `$4`. An existing membership is
+/// only replaced when it expired or the new validity covers it, nothing is
+/// returned when it would be shortened.
+pub(super) const ADD_TO_GROUP_SQL: &str = r#"insert into users_groups
+    (user_id, group_id, valid_from, valid_until)
+values ($1, $2, $3, $4)
+on conflict (user_id, group_id) do update set
+    valid_from = excluded.valid_from,
+    valid_until = excluded.valid_until
+where users_groups.valid_until <= extract(epoch from now())
+    or ((excluded.valid_from is null
+            or excluded.valid_from <= greatest(users_groups.valid_from, extract(epoch from now())))
+        and (excluded.valid_until is null or excluded.valid_until >= users_groups.valid_until))
+returning user_id"#;
+
+/// Invalidates every token issued to a user.
+const BUMP_TOKEN_VERSION_SQL: &str =
+    "update users set token_version = token_version + 1 where id = $1";
+
 #[derive(Clone)]
 pub struct UserRepository {
     db: Pool<sqlx::Postgres>,
@@ -19,70 +40,182 @@ impl UserRepository {
         &self.db
     }
 
-    async fn unsign(&self, user_id: Uuid) -> Result<(), sqlx::Error> {
-        let sql = "delete from users_groups where user_id = $1";
-        match query(sql).bind(user_id).execute(self.db()).await {
-            Ok(_) => Ok(()),
-            Err(sqlx::Error::RowNotFound) => Ok(()),
-            Err(err) => Err(err),
-        }
-    }
-
-    async fn assign(&self, user_id: Uuid, groups: Vec<Uuid>) -> Result<(), sqlx::Error> {
-        self.unsign(user_id).await?;
-        let sql = "insert into users_groups (user_id, group_id) select $1, unnest($2)";
+    /// Makes the groups the only direct groups of the user, memberships that
+    /// are kept are left untouched unless they expired.
+    async fn assign(
+        conn: &mut PgConnection,
+        user_id: Uuid,
+        groups: Vec<Uuid>,
+    ) -> Result<(), sqlx::Error> {
+        let sql = r#"delete from users_groups
+        where user_id = $1
+            and (group_id <> all($2) or valid_until <= extract(epoch from now()))"#;
         query(sql)
             .bind(user_id)
-            .bind(groups)
-            .execute(self.db())
+            .bind(&groups)
+            .execute(&mut *conn)
             .await?;
+        let sql = r#"insert into users_groups (user_id, group_id) select $1, unnest($2)
+        on conflict do nothing"#;
+        query(sql).bind(user_id).bind(groups).execute(conn).await?;
         Ok(())
     }
 
-    async fn groups(&self, user_id: Uuid) -> Result<Vec<Group>, sqlx::Error> {
-        let sql = "select g.* from groups g join users_groups ug on g.id = ug.group_id where ug.user_id = $1";
-        query_as(sql).bind(user_id).fetch_all(self.db()).await
+    pub async fn group_ids(&self, user_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+        Self::group_ids_with(self.db(), user_id).await
+    }
+
+    async fn group_ids_with<'e>(
+        executor: impl PgExecutor<'e>,
+        user_id: Uuid,
+    ) -> Result<Vec<Uuid>, sqlx::Error> {
+        let sql = "select group_id from active_users_groups where user_id = $1";
+        query_scalar(sql).bind(user_id).fetch_all(executor).await
+    }
+
+    pub async fn groups(&self, user_id: Uuid) -> Result<Vec<Group>, sqlx::Error> {
+        let sql = format!(
+            "select {} from groups g join active_users_groups ug on g.id = ug.group_id where ug.user_id = $1",
+            GROUP_COLUMNS
+        );
+        query_as(&sql).bind(user_id).fetch_all(self.db()).await
     }
 
-    pub async fn find_all(&self) -> Result<Vec<User>, sqlx::Error> {
-        let sql = "select * from users";
-        query_as(sql).fetch_all(self.db()).await
+    /// Groups containing the groups of the user, directly or not, that the
+    /// user is not a direct member of.
+    async fn inherited_groups(&self, user_id: Uuid) -> Result<Vec<Group>, sqlx::Error> {
+        let sql = format!(
+            r#"with recursive parents(id) as (
+                select gg.group_id from active_groups_groups gg
+                    join active_users_groups ug on ug.group_id = gg.member_id
+                where ug.user_id = $1
+                union
+                select gg.group_id from active_groups_groups gg
+                    join parents p on gg.member_id = p.id
+            )
+            select {} from groups g join parents p on g.id = p.id
+            where g.id not in (select group_id from active_users_groups where user_id = $1)"#,
+            GROUP_COLUMNS
+        );
+        query_as(&sql).bind(user_id).fetch_all(self.db()).await
     }
 
-    pub async fn find_all_with_groups(&self) -> Result<Vec<UserWithGroups>, sqlx::Error> {
-        let users = self.find_all().await?;
+    /// Next time a membership of the user starts or ends.
+    async fn memberships_change_at(&self, user_id: Uuid) -> Result<Option<i64>, sqlx::Error> {
+        let sql = r#"select min(at) from (
+            select valid_until as at from active_users_groups where user_id = $1
+            union all
+            select valid_from from users_groups
+            where user_id = $1 and valid_from > extract(epoch from now())
+        ) changes"#;
+        query_scalar(sql).bind(user_id).fetch_one(self.db()).await
+    }
+
+    async fn with_groups(&self, user: User) -> Result<UserWithGroups, sqlx::Error> {
+        let groups = self.groups(user.id).await?;
+        let inherited_groups = self.inherited_groups(user.id).await?;
+        let memberships_change_at = self.memberships_change_at(user.id).await?;
+        Ok(UserWithGroups {
+            user,
+            groups,
+            inherited_groups,
+            memberships_change_at,
+        })
+    }
+
+    /// Users of the organization, deleted users are left out unless
+    /// `include_deleted` is set.
+    pub async fn find_all(
+        &self,
+        org_id: Uuid,
+        include_deleted: bool,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let sql = "select * from users where org_id = $1 and ($2 or deleted_at is null)";
+        query_as(sql)
+            .bind(org_id)
+            .bind(include_deleted)
+            .fetch_all(self.db())
+            .await
+    }
+
+    pub async fn find_all_with_groups(
+        &self,
+        org_id: Uuid,
+        include_deleted: bool,
+    ) -> Result<Vec<UserWithGroups>, sqlx::Error> {
+        let users = self.find_all(org_id, include_deleted).await?;
         let mut list = Vec::new();
         for user in users {
-            let groups = self.groups(user.id).await?;
-            list.push(UserWithGroups { user, groups });
+            list.push(self.with_groups(user).await?);
         }
         Ok(list)
     }
 
     pub async fn find(&self, id: Uuid) -> Result<User, sqlx::Error> {
-        let sql = "select * from users where id = $1";
+        let sql = "select * from users where id = $1 and deleted_at is null";
         query_as(sql).bind(id).fetch_one(self.db()).await
     }
 
-    pub async fn find_by_username(&self, username: String) -> Result<UserWithGroups, sqlx::Error> {
-        let sql = "select * from users where username = $1 or email = $1";
-        let user: User = query_as(sql).bind(username).fetch_one(self.db()).await?;
-        let groups = self.groups(user.id).await?;
-        Ok(UserWithGroups { user, groups })
+    /// Returns true if the user belongs to the organization.
+    pub async fn in_organization(&self, id: Uuid, org_id: Uuid) -> Result<bool, sqlx::Error> {
+        let sql = r#"select exists(
+            select 1 from users where id = $1 and org_id = $2 and deleted_at is null
+        )"#;
+        query_scalar(sql)
+            .bind(id)
+            .bind(org_id)
+            .fetch_one(self.db())
+            .await
+    }
+
+    pub async fn find_by_username(
+        &self,
+        org_id: Uuid,
+        username: String,
+    ) -> Result<UserWithGroups, sqlx::Error> {
+        let sql = r#"select * from users
+        where org_id = $1 and (username = $2 or email = $2) and deleted_at is null"#;
+        let user: User = query_as(sql)
+            .bind(org_id)
+            .bind(username)
+            .fetch_one(self.db())
+            .await?;
+        self.with_groups(user).await
+    }
+
+    pub async fn find_by_email(
+        &self,
+        org_id: Uuid,
+        email: String,
+    ) -> Result<UserWithGroups, sqlx::Error> {
+        let sql = "select * from users where org_id = $1 and email = $2 and deleted_at is null";
+        let user: User = query_as(sql)
+            .bind(org_id)
+            .bind(email)
+            .fetch_one(self.db())
+            .await?;
+        self.with_groups(user).await
     }
 
     pub async fn find_with_groups(&self, id: Uuid) -> Result<UserWithGroups, sqlx::Error> {
         let user = self.find(id).await?;
-        let groups = self.groups(id).await?;
-        Ok(UserWithGroups { user, groups })
+        self.with_groups(user).await
     }
 
-    pub async fn create(&self, dto: UserCreateDto) -> Result<UserWithGroups, sqlx::Error> {
+    pub async fn create(
+        &self,
+        org_id: Uuid,
+        dto: UserCreateDto,
+    ) -> Result<UserWithGroups, sqlx::Error> {
+        let mut tx = self.db().begin().await?;
         // create user
         let sql = r#"insert into users 
-            (name, phone, role, email, username, password_hash, visible, editable, locked)
+            (name, phone, role, email, username, password_hash, visible, editable, locked,
+            attributes, org_id, email_verified_at, registered_at)
         values
-            ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, coalesce($10, '{}'), $11,
+            case when $12 then extract(epoch from now()) end,
+            case when $13 then extract(epoch from now()) end)
         returning *"#;
         let user: User = query_as(sql)
             .bind(dto.name)
@@ -94,30 +227,44 @@ impl UserRepository {
             .bind(dto.visible)
             .bind(dto.editable)
             .bind(dto.locked)
-            .fetch_one(self.db())
+            .bind(dto.attributes.map(Json))
+            .bind(org_id)
+            .bind(dto.email_verified)
+            .bind(dto.registered)
+            .fetch_one(&mut *tx)
             .await?;
         // assign groups
-        self.assign(user.id, dto.groups).await?;
+        Self::assign(&mut tx, user.id, dto.groups).await?;
+        tx.commit().await?;
         // get groups
-        let groups = self.groups(user.id).await?;
-        Ok(UserWithGroups { user, groups })
+        self.with_groups(user).await
     }
 
+    /// Updates a user of the organization and its direct groups. Returns
+    /// nothing and changes nothing if it takes `root` away from the last
+    /// active root user.
     pub async fn update(
         &self,
+        org_id: Uuid,
         id: Uuid,
         dto: UserUpdateDto,
-    ) -> Result<UserWithGroups, sqlx::Error> {
+    ) -> Result<Option<UserWithGroups>, sqlx::Error> {
+        let mut guard = RootGuard::begin(self.db(), org_id).await?;
         // update user
         let sql = r#"update users set
             name = $2,
             phone = $3,
             role = $4,
-            email = $5,
+            pending_email = case when $5 = email then null else $5 end,
             username = $6,
             visible = $7,
             editable = $8,
-            locked = $9
+            locked = $9,
+            disabled = coalesce($10, disabled),
+            attributes = coalesce($11, attributes),
+            token_version = token_version + case
+                when locked <> $9 or disabled <> coalesce($10, disabled) then 1 else 0
+            end,
             updated_at = extract(epoch from now())
         where id = $1 returning *"#;
         let user: User = query_as(sql)
@@ -130,18 +277,48 @@ impl UserRepository {
             .bind(dto.visible)
             .bind(dto.editable)
             .bind(dto.locked)
-            .fetch_one(self.db())
+            .bind(dto.disabled)
+            .bind(dto.attributes.map(Json))
+            .fetch_one(guard.conn())
             .await?;
         // assign groups
-        self.assign(user.id, dto.groups).await?;
+        let mut before = Self::group_ids_with(guard.conn(), user.id).await?;
+        Self::assign(guard.conn(), user.id, dto.groups).await?;
+        let mut after = Self::group_ids_with(guard.conn(), user.id).await?;
+        before.sort();
+        after.sort();
+        if before != after {
+            query(BUMP_TOKEN_VERSION_SQL)
+                .bind(user.id)
+                .execute(guard.conn())
+                .await?;
+        }
+        if !guard.commit().await? {
+            return Ok(None);
+        }
+        token_version::invalidate(user.id);
         // get groups
-        let groups = self.groups(user.id).await?;
-        Ok(UserWithGroups { user, groups })
+        self.with_groups(user).await.map(Some)
+    }
+
+    /// Stores a new email address that only replaces the current one once
+    /// it is verified.
+    pub async fn update_pending_email(&self, id: Uuid, email: String) -> Result<User, sqlx::Error> {
+        let sql = r#"update users set
+            pending_email = case when $2 = email then null else $2 end,
+            updated_at = extract(epoch from now())
+        where id = $1 returning *"#;
+        query_as(sql)
+            .bind(id)
+            .bind(email)
+            .fetch_one(self.db())
+            .await
     }
 
     pub async fn update_password(&self, id: Uuid, dto: PasswordDto) -> Result<(), sqlx::Error> {
         let sql = r#"update users set
             password_hash = $2,
+            token_version = token_version + 1,
             updated_at = extract(epoch from now())
         where id = $1"#;
         query(sql)
@@ -149,9 +326,25 @@ impl UserRepository {
             .bind(dto.password_hash)
             .execute(self.db())
             .await?;
+        token_version::invalidate(id);
+        Ok(())
+    }
+
+    /// Invalidates every token issued to the user.
+    pub async fn bump_token_version(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        query(BUMP_TOKEN_VERSION_SQL)
+            .bind(id)
+            .execute(self.db())
+            .await?;
+        token_version::invalidate(id);
         Ok(())
     }
 
+    pub async fn token_version(&self, id: Uuid) -> Result<i32, sqlx::Error> {
+        let sql = "select token_version from users where id = $1";
+        query_scalar(sql).bind(id).fetch_one(self.db()).await
+    }
+
     pub async fn update_profile(&self, id: Uuid, dto: ProfileDto) -> Result<User, sqlx::Error> {
         let sql = r#"update users set name = $2, phone = $3, role = $4, updated_at = extract(epoch from now()) where id = $1 returning *"#;
         query_as(sql)
@@ -165,6 +358,123 @@ impl UserRepository {
         // Ok(UserWithGroups { user, groups })
     }
 
+    /// Adds the user to a group between `valid_from` and `valid_until`, an
+    /// existing membership is replaced unless it is still valid beyond them.
+    /// Returns `false` and changes nothing in that case.
+    pub async fn add_to_group(
+        &self,
+        id: Uuid,
+        group_id: Uuid,
+        valid_from: Option<i64>,
+        valid_until: Option<i64>,
+    ) -> Result<bool, sqlx::Error> {
+        let added: Option<Uuid> = query_scalar(ADD_TO_GROUP_SQL)
+            .bind(id)
+            .bind(group_id)
+            .bind(valid_from)
+            .bind(valid_until)
+            .fetch_optional(self.db())
+            .await?;
+        if added.is_none() {
+            return Ok(false);
+        }
+        self.bump_token_version(id).await?;
+        Ok(true)
+    }
+
+    /// Adds and removes members of a group at once. Returns `false` and
+    /// changes nothing if it takes `root` away from the last active root user.
+    pub async fn update_members(
+        &self,
+        org_id: Uuid,
+        group_id: Uuid,
+        add: &[Uuid],
+        remove: &[Uuid],
+    ) -> Result<bool, sqlx::Error> {
+        let mut guard = RootGuard::begin(self.db(), org_id).await?;
+        let sql = r#"insert into users_groups (user_id, group_id) select unnest($1::uuid[]), $2
+        on conflict do nothing"#;
+        query(sql)
+            .bind(add)
+            .bind(group_id)
+            .execute(guard.conn())
+            .await?;
+        let sql = "delete from users_groups where group_id = $1 and user_id = any($2)";
+        query(sql)
+            .bind(group_id)
+            .bind(remove)
+            .execute(guard.conn())
+            .await?;
+        let sql = "update users set token_version = token_version + 1 where id = any($1)";
+        let changed: Vec<Uuid> = add.iter().chain(remove).cloned().collect();
+        query(sql).bind(&changed).execute(guard.conn()).await?;
+        if !guard.commit().await? {
+            return Ok(false);
+        }
+        for id in changed {
+            token_version::invalidate(id);
+        }
+        Ok(true)
+    }
+
+    /// Takes the user out of the group. Returns `false` and changes nothing
+    /// if it is the last active root user.
+    pub async fn remove_from_group(
+        &self,
+        org_id: Uuid,
+        id: Uuid,
+        group_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let mut guard = RootGuard::begin(self.db(), org_id).await?;
+        let sql = "delete from users_groups where user_id = $1 and group_id = $2 returning user_id";
+        query_scalar::<_, Uuid>(sql)
+            .bind(id)
+            .bind(group_id)
+            .fetch_one(guard.conn())
+            .await?;
+        query(BUMP_TOKEN_VERSION_SQL)
+            .bind(id)
+            .execute(guard.conn())
+            .await?;
+        if !guard.commit().await? {
+            return Ok(false);
+        }
+        token_version::invalidate(id);
+        Ok(true)
+    }
+
+    /// Soft-deletes the user of the organization, its tokens are revoked.
+    /// Returns `false` and changes nothing if it is the last active root user.
+    pub async fn delete(&self, org_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+        let mut guard = RootGuard::begin(self.db(), org_id).await?;
+        self.soft_delete(guard.conn(), org_id, id).await?;
+        query(BUMP_TOKEN_VERSION_SQL)
+            .bind(id)
+            .execute(guard.conn())
+            .await?;
+        if !guard.commit().await? {
+            return Ok(false);
+        }
+        token_version::invalidate(id);
+        Ok(true)
+    }
+
+    /// Groups the deleted user gets back when it is restored, its memberships
+    /// that did not expire in groups that are not deleted.
+    pub async fn restored_group_ids(&self, id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+        let sql = r#"select ug.group_id from users_groups ug
+            join groups g on g.id = ug.group_id
+        where ug.user_id = $1
+            and (ug.valid_until is null or ug.valid_until > extract(epoch from now()))
+            and g.deleted_at is null"#;
+        query_scalar(sql).bind(id).fetch_all(self.db()).await
+    }
+
+    pub async fn is_visible(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let sql = "select exists(select 1 from users where id = $1 and visible)";
+        query_scalar(sql).bind(id).fetch_one(self.db()).await
+    }
+
     pub async fn is_editable(&self, id: Uuid) -> Result<bool, sqlx::Error> {
         let sql = "select exists(select 1 from users where id = $1 and editable)";
         query_scalar(sql).bind(id).fetch_one(self.db()).await
@@ -175,3 +485,13 @@ impl UserRepository {
         query_scalar(sql).bind(id).fetch_one(self.db()).await
     }
 }
+
+impl SoftDelete for UserRepository {
+    fn db(&self) -> &Pool<Postgres> {
+        &self.db
+    }
+
+    fn table(&self) -> &str {
+        "users"
+    }
+}
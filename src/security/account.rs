@@ -0,0 +1,24 @@
+use axum::{http::StatusCode, Json};
+
+use crate::{controller::Errors, model::User};
+
+/// Checks that the account can be used, shared by login, token refresh and
+/// the `Jwt` extractor.
+pub fn check(user: &User) -> Result<(), (StatusCode, Json<Errors>)> {
+    if user.deleted_at.is_some() {
+        return Err(Errors::account_deleted());
+    }
+    if user.disabled {
+        return Err(Errors::account_disabled());
+    }
+    if user.locked {
+        return Err(Errors::account_locked());
+    }
+    Ok(())
+}
+
+/// Returns true if `JWT_CHECK_ACCOUNT_STATUS` makes the `Jwt` extractor load
+/// the user and check its account on every request.
+pub fn checked_on_requests() -> bool {
+    std::env::var("JWT_CHECK_ACCOUNT_STATUS").is_ok_and(|value| value == "true")
+}
@@ -0,0 +1,139 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::LazyLock,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts},
+};
+
+/// Where a request comes from, recorded on the session created by a login.
+///
+/// The address is the peer address, or the one `X-Forwarded-For` tells when
+/// the peer is one of the `TRUSTED_PROXIES`, the device name is sent by
+/// clients in `X-Device-Name`.
+#[derive(Debug, Default)]
+pub struct ClientInfo {
+    pub device: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Addresses of the proxies trusted to set `X-Forwarded-For`, separated by
+/// commas in `TRUSTED_PROXIES`.
+static TRUSTED_PROXIES: LazyLock<Vec<IpAddr>> = LazyLock::new(|| {
+    std::env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|ip| !ip.trim().is_empty())
+        .map(|ip| {
+            ip.trim()
+                .parse()
+                .unwrap_or_else(|err| panic!("invalid TRUSTED_PROXIES entry {}: {}", ip, err))
+        })
+        .collect()
+});
+
+/// Loads the trusted proxies, so an invalid list stops the server when it
+/// starts.
+pub fn init() {
+    LazyLock::force(&TRUSTED_PROXIES);
+}
+
+/// Returns the address of the client, the last address of `forwarded` that is
+/// not a trusted proxy when the peer is one, since the proxies append the
+/// address they received the request from and anything before can be forged.
+fn client_ip(peer: Option<IpAddr>, forwarded: Option<&str>, trusted: &[IpAddr]) -> Option<String> {
+    let peer = peer?;
+    if !trusted.contains(&peer) {
+        return Some(peer.to_string());
+    }
+    let forwarded = forwarded.unwrap_or_default().split(',').map(str::trim);
+    let mut client = peer.to_string();
+    for ip in forwarded.rev() {
+        match ip.parse::<IpAddr>() {
+            Ok(ip) if trusted.contains(&ip) => client = ip.to_string(),
+            Ok(ip) => return Some(ip.to_string()),
+            Err(_) => break,
+        }
+    }
+    Some(client)
+}
+
+fn header(parts: &Parts, name: &str, max: usize) -> Option<String> {
+    parts
+        .headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(max).collect())
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let forwarded = header(parts, "x-forwarded-for", 255);
+        let ip = client_ip(peer, forwarded.as_deref(), &TRUSTED_PROXIES);
+        Ok(ClientInfo {
+            device: header(parts, "x-device-name", 100),
+            ip: ip.map(|ip| ip.chars().take(45).collect()),
+            user_agent: header(parts, USER_AGENT.as_str(), 255),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_forwarded_from_untrusted_peers() {
+        let trusted = [ip("10.0.0.1")];
+        assert_eq!(
+            client_ip(Some(ip("203.0.113.7")), Some("1.2.3.4"), &trusted),
+            Some(String::from("203.0.113.7"))
+        );
+        assert_eq!(
+            client_ip(Some(ip("203.0.113.7")), Some("1.2.3.4"), &[]),
+            Some(String::from("203.0.113.7"))
+        );
+    }
+
+    #[test]
+    fn reads_forwarded_from_trusted_peers() {
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), Some("1.2.3.4"), &trusted),
+            Some(String::from("1.2.3.4"))
+        );
+        // the client forged the first entry
+        assert_eq!(
+            client_ip(
+                Some(ip("10.0.0.1")),
+                Some("6.6.6.6, 1.2.3.4, 10.0.0.2"),
+                &trusted
+            ),
+            Some(String::from("1.2.3.4"))
+        );
+        assert_eq!(
+            client_ip(Some(ip("10.0.0.1")), None, &trusted),
+            Some(String::from("10.0.0.1"))
+        );
+        assert_eq!(client_ip(None, Some("1.2.3.4"), &trusted), None);
+    }
+}
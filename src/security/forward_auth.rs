@@ -0,0 +1,243 @@
+//! Route rules of the forward-auth endpoint.
+//!
+//! Rules are loaded from the JSON array in `FORWARD_AUTH_RULES` and tell the
+//! permission required by the requests a proxy forwards, e.g.
+//!
+//! ```json
+//! [
+//!     { "host": "grafana.internal", "permission": "grafana:read" },
+//!     { "path": "/admin", "methods": ["POST", "PUT"], "permission": "dashboard:update" }
+//! ]
+//! ```
+//!
+//! A rule matches a request when its host is the host of the request, its
+//! path is the path or a parent of it, and its methods contain the method,
+//! omitted fields match anything. The first rule matching decides.
+
+use std::{fs, sync::LazyLock};
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct RouteRule {
+    pub host: Option<String>,
+    pub path: Option<String>,
+    #[serde(default)]
+    pub methods: Vec<String>,
+    pub permission: String,
+}
+
+impl RouteRule {
+    fn matches(&self, host: Option<&str>, method: Option<&str>, path: &str) -> bool {
+        let host_matches = match (&self.host, host) {
+            (None, _) => true,
+            (Some(expected), Some(host)) => expected.eq_ignore_ascii_case(host),
+            (Some(_), None) => false,
+        };
+        let method_matches = self.methods.is_empty()
+            || method.is_some_and(|method| {
+                self.methods
+                    .iter()
+                    .any(|expected| expected.eq_ignore_ascii_case(method))
+            });
+        host_matches && method_matches && self.path.as_deref().is_none_or(|p| under(p, path))
+    }
+}
+
+/// Returns true if `path` is `prefix` or below it, `/admin` covers
+/// `/admin/users` but not `/administration`.
+fn under(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    match path.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Path of the URI the way the upstream service resolves it, without the
+/// query, percent-decoded, with `//`, `.` and `..` resolved, so `//admin` or
+/// `/x/../admin` can not get around a rule on `/admin`.
+fn normalize(uri: &str) -> String {
+    let path = uri.split(['?', '#']).next().unwrap_or_default();
+    // encoded twice, e.g. %252e, is decoded until nothing changes
+    let mut decoded = String::from(path);
+    for _ in 0..3 {
+        let next = decode(&decoded);
+        if next == decoded {
+            break;
+        }
+        decoded = next;
+    }
+    let mut segments: Vec<&str> = vec![];
+    for segment in decoded.split(['/', '\\']) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+static RULES: LazyLock<Vec<RouteRule>> = LazyLock::new(from_file);
+
+/// Reads the rules in `FORWARD_AUTH_RULES`, if set.
+fn from_file() -> Vec<RouteRule> {
+    let Ok(path) = std::env::var("FORWARD_AUTH_RULES") else {
+        return vec![];
+    };
+    let data = fs::read(&path).unwrap_or_else(|err| panic!("failed to read {}: {}", path, err));
+    serde_json::from_slice(&data)
+        .unwrap_or_else(|err| panic!("invalid forward-auth rules in {}: {}", path, err))
+}
+
+/// Loads the rules, so an invalid file stops the server when it starts.
+pub fn init() {
+    LazyLock::force(&RULES);
+}
+
+/// Returns the permission the configured rules require for the request,
+/// `path` may be the raw URI forwarded by the proxy.
+pub fn required_permission(
+    host: Option<&str>,
+    method: Option<&str>,
+    path: &str,
+) -> Option<&'static str> {
+    find(&RULES, host, method, path)
+}
+
+fn find<'a>(
+    rules: &'a [RouteRule],
+    host: Option<&str>,
+    method: Option<&str>,
+    path: &str,
+) -> Option<&'a str> {
+    let path = normalize(path);
+    rules
+        .iter()
+        .find(|rule| rule.matches(host, method, &path))
+        .map(|rule| rule.permission.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> Vec<RouteRule> {
+        serde_json::from_str(
+            r#"[
+                { "host": "grafana.internal", "permission": "grafana:read" },
+                { "path": "/admin", "methods": ["POST"], "permission": "dashboard:update" },
+                { "path": "/admin", "permission": "dashboard:read" }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_host() {
+        let rules = rules();
+        assert_eq!(
+            find(&rules, Some("Grafana.Internal"), Some("GET"), "/"),
+            Some("grafana:read")
+        );
+        assert_eq!(find(&rules, Some("other.internal"), Some("GET"), "/"), None);
+    }
+
+    #[test]
+    fn matches_path_and_method() {
+        let rules = rules();
+        assert_eq!(
+            find(&rules, None, Some("post"), "/admin/users"),
+            Some("dashboard:update")
+        );
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/admin"),
+            Some("dashboard:read")
+        );
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/admin?tab=users"),
+            Some("dashboard:read")
+        );
+        assert_eq!(find(&rules, None, Some("GET"), "/administration"), None);
+        assert_eq!(find(&rules, None, None, "/admin/"), Some("dashboard:read"));
+    }
+
+    #[test]
+    fn matches_doubled_slashes() {
+        let rules = rules();
+        assert_eq!(
+            find(&rules, None, Some("GET"), "//admin"),
+            Some("dashboard:read")
+        );
+        assert_eq!(find(&rules, None, Some("GET"), "/x//admin/users"), None);
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/admin//users"),
+            Some("dashboard:read")
+        );
+    }
+
+    #[test]
+    fn matches_dot_segments() {
+        let rules = rules();
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/x/../admin"),
+            Some("dashboard:read")
+        );
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/./admin/users"),
+            Some("dashboard:read")
+        );
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/../../admin"),
+            Some("dashboard:read")
+        );
+        assert_eq!(find(&rules, None, Some("GET"), "/admin/../public"), None);
+    }
+
+    #[test]
+    fn matches_percent_encoded_paths() {
+        let rules = rules();
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/%61dmin"),
+            Some("dashboard:read")
+        );
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/x/%2e%2e/admin"),
+            Some("dashboard:read")
+        );
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/x%2F..%2Fadmin"),
+            Some("dashboard:read")
+        );
+        assert_eq!(
+            find(&rules, None, Some("GET"), "/x/%252e%252e/admin"),
+            Some("dashboard:read")
+        );
+    }
+}
@@ -2,7 +2,7 @@ use std::fs;
 
 use axum::{
     async_trait,
-    extract::FromRequestParts,
+    extract::{FromRef, FromRequestParts},
     http::{request::Parts, StatusCode},
     Json, RequestPartsExt,
 };
@@ -12,45 +12,163 @@ use axum_extra::{
     TypedHeader,
 };
 
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use chrono::Utc;
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
+pub use gaia_auth_client::Claims;
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse,
+        RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rsa::{
+    pkcs1::DecodeRsaPublicKey, pkcs8::DecodePublicKey, traits::PublicKeyParts, RsaPublicKey,
+};
 
-use crate::{controller::Errors, model::UserWithGroups};
-static PERMISSIONS: &[&str] = &["root", "admin"];
+use crate::{
+    controller::Errors,
+    model::{UserWithGroups, SYSTEM_ORGANIZATION},
+    repository::{OrganizationRepository, SessionRepository, UserRepository},
+    security::{account, mfa::MFA_ENROLLMENT_SCOPE, permission, token_version, verification},
+};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub iss: String,
-    pub sub: String,
-    pub exp: i64,
-    pub iat: i64,
-    pub groups: Vec<String>,
-}
+/// Lifetime in seconds of the access tokens.
+pub const TOKEN_TTL: i64 = 60 * 60 * 24; // 1 day
+
+/// Header super-admins set to the id of the organization they act in.
+pub const ORGANIZATION_HEADER: &str = "x-organization";
 
 pub struct Jwt {
     pub id: uuid::Uuid,
     pub perms: Vec<String>,
+    pub session_id: Option<uuid::Uuid>,
+    /// Organization the request acts in, the one of the user unless a
+    /// super-admin selected another one with `X-Organization`.
+    pub org_id: uuid::Uuid,
+    super_admin: bool,
 }
 
-pub fn generate_token(user: &UserWithGroups) -> Result<String, (StatusCode, Json<Errors>)> {
+pub fn generate_token(
+    user: &UserWithGroups,
+    session_id: uuid::Uuid,
+) -> Result<String, (StatusCode, Json<Errors>)> {
     let now = Utc::now().timestamp();
-    let exp = now + 60 * 60 * 24; // 1 day
-    let header = Header::new(Algorithm::RS256);
-    let private_key_file = std::env::var("JWT_PRIVATE_KEY").unwrap_or(String::from("private.pem"));
+    let exp = match user.memberships_change_at {
+        Some(at) => at.min(now + TOKEN_TTL),
+        None => now + TOKEN_TTL,
+    };
+    let claims = Claims {
+        iss: issuer(),
+        aud: audience(),
+        sub: user.user.id.to_string(),
+        exp,
+        iat: now,
+        groups: permissions(user),
+        scope: None,
+        ver: user.user.token_version,
+        sid: Some(session_id.to_string()),
+        org: Some(user.user.org_id.to_string()),
+    };
+    encode(&claims)
+}
+
+/// Permissions given to the user by its groups, with the deny entries of the
+/// email policy while its address is not verified.
+pub fn permissions(user: &UserWithGroups) -> Vec<String> {
     let mut permissions = vec![];
 
-    for group in &user.groups {
+    for group in user.all_groups() {
         permissions.append(&mut group.permissions());
     }
 
+    if user.user.email_verified_at.is_none() {
+        permissions.append(&mut verification::policy().deny_entries());
+    }
+    permissions
+}
+
+/// Generates a short lived token that only grants access to `scope`, it is
+/// rejected by the `Jwt` extractor.
+pub fn generate_scoped_token(
+    user_id: uuid::Uuid,
+    scope: &str,
+    ttl: i64,
+) -> Result<String, (StatusCode, Json<Errors>)> {
+    let now = Utc::now().timestamp();
     let claims = Claims {
-        iss: std::env::var("JWT_ISSUER").unwrap_or(String::from("gaia")),
-        sub: user.user.id.to_string(),
-        exp,
+        iss: issuer(),
+        aud: audience(),
+        sub: user_id.to_string(),
+        exp: now + ttl,
         iat: now,
-        groups: permissions,
+        groups: vec![],
+        scope: Some(String::from(scope)),
+        ver: 0,
+        sid: None,
+        org: None,
     };
+    encode(&claims)
+}
+
+/// Verifies a token generated by `generate_scoped_token` and returns the user id.
+pub fn verify_scoped_token(
+    token: &str,
+    scope: &str,
+) -> Result<uuid::Uuid, (StatusCode, Json<Errors>)> {
+    let claims = verify_token(token).map_err(|_| Errors::unauthorized("invalid token"))?;
+    if claims.scope.as_deref() != Some(scope) {
+        return Err(Errors::unauthorized("invalid token scope"));
+    }
+    uuid::Uuid::parse_str(&claims.sub).map_err(|err| Errors::internal(&err.to_string()))
+}
+
+fn issuer() -> String {
+    std::env::var("JWT_ISSUER").unwrap_or(String::from("gaia"))
+}
+
+fn audience() -> Option<String> {
+    std::env::var("JWT_AUDIENCE").ok()
+}
+
+/// Id of the signing key in the token headers and in the key set.
+fn key_id() -> String {
+    std::env::var("JWT_KEY_ID").unwrap_or(String::from("gaia"))
+}
+
+fn public_key() -> Result<Vec<u8>, (StatusCode, Json<Errors>)> {
+    let public_key_file = std::env::var("JWT_PUBLIC_KEY").unwrap_or(String::from("public.pem"));
+    fs::read(public_key_file).map_err(|err| Errors::internal(&err.to_string()))
+}
+
+/// The public key verifying the tokens as a JSON Web Key Set, fetched by the
+/// services using `gaia-auth-client`.
+pub fn key_set() -> Result<JwkSet, (StatusCode, Json<Errors>)> {
+    let data = public_key()?;
+    let pem = String::from_utf8_lossy(&data);
+    let key = RsaPublicKey::from_public_key_pem(&pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(&pem))
+        .map_err(|err| Errors::internal(&err.to_string()))?;
+    let jwk = Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(KeyAlgorithm::RS256),
+            key_id: Some(key_id()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+            e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+        }),
+    };
+    Ok(JwkSet { keys: vec![jwk] })
+}
+
+fn encode(claims: &Claims) -> Result<String, (StatusCode, Json<Errors>)> {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(key_id());
+    let private_key_file = std::env::var("JWT_PRIVATE_KEY").unwrap_or(String::from("private.pem"));
 
     let data = match fs::read(private_key_file) {
         Ok(data) => data,
@@ -62,51 +180,175 @@ pub fn generate_token(user: &UserWithGroups) -> Result<String, (StatusCode, Json
         Err(err) => return Err(Errors::internal(&err.to_string())),
     };
 
-    match jsonwebtoken::encode(&header, &claims, &private_key) {
+    match jsonwebtoken::encode(&header, claims, &private_key) {
         Ok(token) => Ok(token),
         Err(err) => Err(Errors::internal(&err.to_string())),
     }
 }
 
 pub fn verify_token(token: &str) -> Result<Claims, (StatusCode, Json<Errors>)> {
-    let public_key_file = std::env::var("JWT_PUBLIC_KEY").unwrap_or(String::from("public.pem"));
-    let data = match fs::read(public_key_file) {
-        Ok(data) => data,
-        Err(err) => return Err(Errors::internal(&err.to_string())),
-    };
+    let data = public_key()?;
     let key = DecodingKey::from_rsa_pem(&data).map_err(|err| Errors::internal(&err.to_string()))?;
 
-    match jsonwebtoken::decode(token, &key, &Validation::new(Algorithm::RS256)) {
+    let mut validation = Validation::new(Algorithm::RS256);
+    match audience() {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+    match jsonwebtoken::decode(token, &key, &validation) {
         Ok(token) => Ok(token.claims),
-        Err(err) => Err(Errors::internal(&err.to_string())),
+        Err(err) => Err(Errors::unauthorized(&err.to_string())),
+    }
+}
+
+/// Same as `Jwt`, but also accepts the enrollment-only token returned by
+/// `login` to users of groups that require MFA.
+pub struct EnrollmentJwt {
+    pub id: uuid::Uuid,
+}
+
+async fn bearer_claims(parts: &mut Parts) -> Result<Claims, (StatusCode, Json<Errors>)> {
+    let TypedHeader(Authorization(bearer)) = parts
+        .extract::<TypedHeader<Authorization<Bearer>>>()
+        .await
+        .map_err(|err| Errors::unauthorized(&err.to_string()))?;
+    verify_token(bearer.token())
+}
+
+/// Verifies an access token like the `Jwt` extractor, the caller acts in the
+/// organization of the user.
+pub async fn authenticate<S>(token: &str, state: &S) -> Result<Jwt, (StatusCode, Json<Errors>)>
+where
+    UserRepository: FromRef<S>,
+    SessionRepository: FromRef<S>,
+{
+    let claims = verify_token(token)?;
+    if claims.scope.is_some() {
+        return Err(Errors::unauthorized("invalid token scope"));
+    }
+    let id: uuid::Uuid =
+        uuid::Uuid::parse_str(&claims.sub).map_err(|err| Errors::internal(&err.to_string()))?;
+    let version = match token_version::get(id) {
+        Some(version) => version,
+        None => {
+            let repo = UserRepository::from_ref(state);
+            let version = match repo.token_version(id).await {
+                Ok(version) => version,
+                Err(sqlx::Error::RowNotFound) => return Err(Errors::unauthorized("invalid token")),
+                Err(err) => return Err(Errors::sql(err)),
+            };
+            token_version::set(id, version);
+            version
+        }
+    };
+    if claims.ver != version {
+        return Err(Errors::unauthorized("token was revoked"));
+    }
+    if account::checked_on_requests() {
+        let user = match UserRepository::from_ref(state).find(id).await {
+            Ok(user) => user,
+            Err(sqlx::Error::RowNotFound) => return Err(Errors::unauthorized("invalid token")),
+            Err(err) => return Err(Errors::sql(err)),
+        };
+        account::check(&user)?;
     }
+    let session_id = match &claims.sid {
+        Some(sid) => {
+            let sid =
+                uuid::Uuid::parse_str(sid).map_err(|_| Errors::unauthorized("invalid token"))?;
+            let sessions = SessionRepository::from_ref(state);
+            if !sessions.touch(sid, id).await.map_err(Errors::sql)? {
+                return Err(Errors::unauthorized("session was revoked"));
+            }
+            Some(sid)
+        }
+        None => None,
+    };
+    let org_id = match &claims.org {
+        Some(org) => {
+            uuid::Uuid::parse_str(org).map_err(|_| Errors::unauthorized("invalid token"))?
+        }
+        None => SYSTEM_ORGANIZATION,
+    };
+    let super_admin = org_id == SYSTEM_ORGANIZATION
+        && permission::allows(&claims.groups, permission::SUPER_ADMIN);
+    Ok(Jwt {
+        id,
+        perms: claims.groups,
+        session_id,
+        org_id,
+        super_admin,
+    })
 }
 
 #[async_trait]
 impl<S> FromRequestParts<S> for Jwt
 where
+    UserRepository: FromRef<S>,
+    SessionRepository: FromRef<S>,
+    OrganizationRepository: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = (StatusCode, Json<Errors>);
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         let TypedHeader(Authorization(bearer)) = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|err| Errors::unauthorized(&err.to_string()))?;
-        let claims = verify_token(bearer.token())?;
+        let mut jwt = authenticate(bearer.token(), state).await?;
+        if let Some(value) = parts.headers.get(ORGANIZATION_HEADER) {
+            if !jwt.super_admin {
+                return Err(Errors::forbidden());
+            }
+            let org_id = value
+                .to_str()
+                .ok()
+                .and_then(|value| uuid::Uuid::parse_str(value).ok())
+                .ok_or_else(|| Errors::unprocessable("invalid organization"))?;
+            let organizations = OrganizationRepository::from_ref(state);
+            if !organizations.exists(org_id).await.map_err(Errors::sql)? {
+                return Err(Errors::not_found());
+            }
+            jwt.org_id = org_id;
+        }
+        Ok(jwt)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for EnrollmentJwt
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, Json<Errors>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let claims = bearer_claims(parts).await?;
+        match claims.scope.as_deref() {
+            None | Some(MFA_ENROLLMENT_SCOPE) => {}
+            Some(_) => return Err(Errors::unauthorized("invalid token scope")),
+        }
         let id: uuid::Uuid =
             uuid::Uuid::parse_str(&claims.sub).map_err(|err| Errors::internal(&err.to_string()))?;
-        Ok(Jwt {
-            id,
-            perms: claims.groups,
-        })
+        Ok(EnrollmentJwt { id })
     }
 }
 
 impl Jwt {
-    fn has_admin(&self) -> bool {
-        self.perms.iter().any(|p| PERMISSIONS.contains(&p.as_str()))
+    /// Same as the token of the user, used to check the permissions of a user
+    /// without one.
+    pub fn for_user(user: &UserWithGroups) -> Self {
+        let perms = permissions(user);
+        let super_admin = user.user.org_id == SYSTEM_ORGANIZATION
+            && permission::allows(&perms, permission::SUPER_ADMIN);
+        Jwt {
+            id: user.user.id,
+            perms,
+            session_id: None,
+            org_id: user.user.org_id,
+            super_admin,
+        }
     }
 
     pub fn is_root(&self) -> bool {
@@ -117,17 +359,27 @@ impl Jwt {
         self.perms.contains(&String::from("admin")) || self.is_root()
     }
 
+    /// See `security::permission` for how grants are matched.
     pub fn has_permission(&self, permission: &str) -> bool {
-        if !self.perms.contains(&String::from(permission)) {
-            return self.has_admin();
-        }
-        true
+        permission::allows(&self.perms, permission)
     }
 
-    pub fn has_permissions(&self, permissions: &[&str]) -> bool {
-        if !self.perms.iter().any(|p| permissions.contains(&p.as_str())) {
-            return self.has_admin();
+    /// Returns true if the caller holds `superadmin` in the system
+    /// organization, super-admins work across organizations.
+    pub fn is_super_admin(&self) -> bool {
+        self.super_admin
+    }
+
+    /// Returns true if the caller may grant `permission` to others, only root
+    /// can grant `root`, only super-admins `superadmin`, and deny entries can
+    /// always be given.
+    pub fn can_grant(&self, permission: &str) -> bool {
+        if permission == permission::SUPER_ADMIN {
+            return self.is_super_admin();
+        }
+        if self.is_root() || permission.starts_with(permission::DENY_PREFIX) {
+            return true;
         }
-        true
+        permission != "root" && self.has_permission(permission)
     }
 }
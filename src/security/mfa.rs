@@ -0,0 +1,37 @@
+use rand::Rng;
+
+/// Scope of the token returned by `login` when a second factor is required.
+pub const MFA_SCOPE: &str = "mfa";
+/// Lifetime in seconds of the token used to complete the second login step.
+pub const MFA_TOKEN_TTL: i64 = 60 * 5;
+/// Scope of the token returned by `login` to users that must enroll in MFA
+/// before getting an access token.
+pub const MFA_ENROLLMENT_SCOPE: &str = "mfa_enroll";
+/// Lifetime in seconds of the enrollment-only token.
+pub const MFA_ENROLLMENT_TOKEN_TTL: i64 = 60 * 15;
+
+const RECOVERY_CODES: usize = 10;
+const RECOVERY_CODE_CHARS: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Generates a new set of recovery codes formatted as `xxxxx-xxxxx`.
+pub fn recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let code: String = (0..10)
+                .map(|_| RECOVERY_CODE_CHARS[rng.gen_range(0..RECOVERY_CODE_CHARS.len())] as char)
+                .collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Hashes a recovery code, ignoring case, spaces and dashes typed by the user.
+pub fn hash_recovery_code(code: &str) -> Result<Vec<u8>, argon2::Error> {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    super::password::hash(&code)
+}
@@ -1,4 +1,15 @@
+pub mod account;
+pub mod client;
+pub mod forward_auth;
 pub mod jwt;
+pub mod mfa;
 pub mod password;
+pub mod passwordless;
+pub mod permission;
+pub mod policy;
+pub mod scope;
+pub mod token_version;
+pub mod verification;
 
-pub use jwt::Jwt;
+pub use client::ClientInfo;
+pub use jwt::{EnrollmentJwt, Jwt};
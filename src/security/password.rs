@@ -1,3 +1,5 @@
+use std::sync::LazyLock;
+
 use argon2::{self, Config};
 
 pub fn hash(password: &str) -> Result<Vec<u8>, argon2::Error> {
@@ -17,3 +19,41 @@ pub fn check(password_hash: &[u8], password: &str) -> Result<bool, argon2::Error
 pub fn hex_to_bytes(hex: &str) -> Vec<u8> {
     hex::decode(hex).expect("failed to decode salt hex")
 }
+
+/// Bounds of the password length, set with `PASSWORD_MIN_LENGTH` (default 8)
+/// and `PASSWORD_MAX_LENGTH` (default 128).
+static LENGTH: LazyLock<(usize, usize)> = LazyLock::new(|| {
+    (
+        length_from_env("PASSWORD_MIN_LENGTH", 8),
+        length_from_env("PASSWORD_MAX_LENGTH", 128),
+    )
+});
+
+/// Reads the password policy, so an invalid value stops the server when it
+/// starts.
+pub fn init() {
+    LazyLock::force(&LENGTH);
+}
+
+/// Checks a new password against the password policy.
+pub fn validate(password: &str) -> Result<(), String> {
+    let (min, max) = *LENGTH;
+    let length = password.chars().count();
+    if length < min {
+        return Err(format!("password must have at least {} characters", min));
+    }
+    if length > max {
+        return Err(format!("password must have at most {} characters", max));
+    }
+    Ok(())
+}
+
+fn length_from_env(key: &str, default: usize) -> usize {
+    std::env::var(key)
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be a number", key))
+        })
+        .unwrap_or(default)
+}
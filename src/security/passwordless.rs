@@ -0,0 +1,24 @@
+use chrono::Utc;
+use rand::Rng;
+
+/// Generates a 6 digit login code.
+pub fn code() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+/// Returns the expiration of a login link or code, the lifetime in seconds
+/// is set with `LOGIN_TOKEN_TTL` and defaults to ten minutes.
+pub fn expires_at() -> i64 {
+    let ttl = std::env::var("LOGIN_TOKEN_TTL")
+        .map(|ttl| ttl.parse().expect("LOGIN_TOKEN_TTL must be a number"))
+        .unwrap_or(60 * 10);
+    Utc::now().timestamp() + ttl
+}
+
+/// Returns the login link sent to the user, `LOGIN_LINK_URL` should point to
+/// a page that sends the token to `POST /login/link`.
+pub fn link(token: &str) -> String {
+    let url =
+        std::env::var("LOGIN_LINK_URL").unwrap_or(String::from("http://localhost:4000/login/link"));
+    format!("{}?token={}", url, token)
+}
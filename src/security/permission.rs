@@ -0,0 +1,68 @@
+//! Permissions of this service.
+//!
+//! Matching lives in `gaia_auth_client::permission` so the services verifying
+//! tokens with the client check them the same way.
+
+pub use gaia_auth_client::permission::{allows, check, matches, Reason, DENY_PREFIX};
+
+/// Permission of the super-admins, only effective in the system organization.
+pub const SUPER_ADMIN: &str = "superadmin";
+
+/// Name of this service in the permissions registry.
+pub const SERVICE: &str = "gaia";
+
+/// Permissions checked by this service, registered at startup.
+pub const PERMISSIONS: &[(&str, &str)] = &[
+    ("root", "everything, even what is denied"),
+    ("admin", "everything that is not denied"),
+    ("nobody", "nothing"),
+    (
+        "superadmin",
+        "every organization, only in the system organization",
+    ),
+    ("user:read", "list and show users"),
+    ("user:create", "create users"),
+    ("user:update", "update users and their sessions"),
+    ("user:delete", "delete and restore users"),
+    ("group:read", "list and show groups"),
+    ("group:create", "create groups"),
+    ("group:update", "update groups and the groups they contain"),
+    ("group:delete", "delete and restore groups"),
+    ("permission:read", "list the registered permissions"),
+    (
+        "authorization:check",
+        "check the permissions of other users with POST /authorize",
+    ),
+    (
+        "permission:register",
+        "register the permissions of a service",
+    ),
+];
+
+/// Returns true if a grant refers to registered permissions, a wildcard must
+/// match at least one of them.
+pub fn is_registered<S: AsRef<str>>(registry: &[S], grant: &str) -> bool {
+    let grant = grant.strip_prefix(DENY_PREFIX).unwrap_or(grant);
+    registry
+        .iter()
+        .any(|permission| matches(grant, permission.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_grants() {
+        let registry = ["user:read", "user:update", "group:read"];
+        assert!(is_registered(&registry, "user:read"));
+        assert!(is_registered(&registry, "!user:update"));
+        assert!(is_registered(&registry, "user:*"));
+        assert!(is_registered(&registry, "*:read"));
+        assert!(is_registered(&registry, "*"));
+        assert!(!is_registered(&registry, "user:reed"));
+        assert!(!is_registered(&registry, "!user:delete"));
+        assert!(!is_registered(&registry, "session:*"));
+        assert!(!is_registered(&registry, "*:delete"));
+    }
+}
@@ -0,0 +1,458 @@
+//! Attribute based access control.
+//!
+//! Rules are loaded from the JSON array in `POLICY_FILE` and from the enabled
+//! rows of the `policies` table. A rule applies to the permissions matching
+//! its `permission` and holds when all its conditions hold, e.g.
+//!
+//! ```json
+//! {
+//!     "name": "support reads users of its department",
+//!     "effect": "allow",
+//!     "permission": "user:read",
+//!     "conditions": [
+//!         { "left": { "attr": "subject.groups" }, "op": "contains", "right": "support" },
+//!         { "left": { "attr": "resource.attributes.department" }, "op": "eq",
+//!           "right": { "attr": "subject.attributes.department" } }
+//!     ]
+//! }
+//! ```
+//!
+//! Attributes are looked up by path in `subject` (the caller with its groups
+//! and permissions), `resource` and `context` (time of the request and
+//! client address). A deny rule that holds refuses the permission, otherwise
+//! an allow rule that holds grants it, otherwise the group permissions decide.
+
+use std::{fs, sync::Arc};
+
+use chrono::{Datelike, FixedOffset, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::model::UserWithGroups;
+
+use super::{permission, ClientInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub effect: Effect,
+    /// Permissions the rule applies to, may contain wildcards.
+    pub permission: String,
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Condition {
+    pub left: Operand,
+    pub op: Operator,
+    pub right: Operand,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Operand {
+    /// Path of an attribute, e.g. `subject.attributes.department`.
+    Attribute {
+        attr: String,
+    },
+    Value(Value),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operator {
+    Eq,
+    Ne,
+    /// The left value is an element of the right array.
+    In,
+    /// The left array has the right value as element.
+    Contains,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    Deny,
+    NotApplicable,
+}
+
+#[derive(Clone)]
+pub struct PolicyEngine {
+    rules: Arc<Vec<Rule>>,
+    /// Offset of the times in `context`, set in hours with
+    /// `POLICY_UTC_OFFSET`, UTC by default.
+    offset: FixedOffset,
+}
+
+impl PolicyEngine {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let hours: i32 = std::env::var("POLICY_UTC_OFFSET")
+            .map(|offset| offset.parse().expect("POLICY_UTC_OFFSET must be a number"))
+            .unwrap_or(0);
+        PolicyEngine {
+            rules: Arc::new(rules),
+            offset: FixedOffset::east_opt(hours * 3600).expect("POLICY_UTC_OFFSET is out of range"),
+        }
+    }
+
+    /// Returns true if a rule may change the decision for `permission`, the
+    /// attributes only need to be gathered in that case.
+    pub fn applies_to(&self, permission: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| permission::matches(&rule.permission, permission))
+    }
+
+    pub fn evaluate(&self, permission: &str, attributes: &Value) -> Decision {
+        match self.explain(permission, attributes) {
+            Some(rule) if rule.effect == Effect::Deny => Decision::Deny,
+            Some(_) => Decision::Allow,
+            None => Decision::NotApplicable,
+        }
+    }
+
+    /// Returns the rule deciding for `permission`, the first deny rule that
+    /// holds or else the first allow rule that holds.
+    pub fn explain(&self, permission: &str, attributes: &Value) -> Option<&Rule> {
+        let mut decision = None;
+        for rule in self.rules.iter() {
+            if !permission::matches(&rule.permission, permission) {
+                continue;
+            }
+            if !rule.conditions.iter().all(|c| c.holds(attributes)) {
+                continue;
+            }
+            match rule.effect {
+                Effect::Deny => return Some(rule),
+                Effect::Allow => decision = decision.or(Some(rule)),
+            }
+        }
+        decision
+    }
+
+    /// Combines the policies with `granted`, the result of
+    /// `Jwt::has_permission`.
+    pub fn check<S: AsRef<str>>(
+        &self,
+        permission: &str,
+        grants: &[S],
+        granted: bool,
+        attributes: &Value,
+    ) -> bool {
+        match self.deciding_rule(permission, grants, attributes) {
+            Some(rule) => rule.effect == Effect::Allow,
+            None => granted,
+        }
+    }
+
+    /// Returns the rule overriding the grants for `permission`, the one of
+    /// `explain` unless it is an allow rule and `grants` deny the permission.
+    /// Allow rules do not override deny entries.
+    pub fn deciding_rule<S: AsRef<str>>(
+        &self,
+        permission: &str,
+        grants: &[S],
+        attributes: &Value,
+    ) -> Option<&Rule> {
+        let denied = matches!(
+            permission::check(grants, permission),
+            permission::Reason::Denied(_)
+        );
+        self.explain(permission, attributes)
+            .filter(|rule| rule.effect == Effect::Deny || !denied)
+    }
+
+    /// Attributes of the request: its time in the configured offset and the
+    /// client address.
+    pub fn context(&self, client: &ClientInfo) -> Value {
+        let now = Utc::now().with_timezone(&self.offset);
+        json!({
+            "timestamp": now.timestamp(),
+            "weekday": now.weekday().number_from_monday(),
+            "hour": now.hour(),
+            "time": now.format("%H:%M").to_string(),
+            "ip": client.ip,
+        })
+    }
+}
+
+impl Condition {
+    fn holds(&self, attributes: &Value) -> bool {
+        let (Some(left), Some(right)) = (
+            self.left.resolve(attributes),
+            self.right.resolve(attributes),
+        ) else {
+            return false;
+        };
+        match self.op {
+            Operator::Eq => left == right,
+            Operator::Ne => left != right,
+            Operator::In => right.as_array().is_some_and(|list| list.contains(left)),
+            Operator::Contains => left.as_array().is_some_and(|list| list.contains(right)),
+            Operator::Gt => compare(left, right).is_some_and(|o| o.is_gt()),
+            Operator::Gte => compare(left, right).is_some_and(|o| o.is_ge()),
+            Operator::Lt => compare(left, right).is_some_and(|o| o.is_lt()),
+            Operator::Lte => compare(left, right).is_some_and(|o| o.is_le()),
+        }
+    }
+}
+
+impl Operand {
+    /// Returns the value of the operand, missing attributes never match.
+    fn resolve<'a>(&'a self, attributes: &'a Value) -> Option<&'a Value> {
+        match self {
+            Operand::Attribute { attr } => attr
+                .split('.')
+                .try_fold(attributes, |value, key| value.get(key))
+                .filter(|value| !value.is_null()),
+            Operand::Value(value) => Some(value),
+        }
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<std::cmp::Ordering> {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.as_f64()?.partial_cmp(&right.as_f64()?),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
+
+/// Reads the rules in `POLICY_FILE`, if set.
+pub fn from_file() -> Vec<Rule> {
+    let Ok(path) = std::env::var("POLICY_FILE") else {
+        return vec![];
+    };
+    let data = fs::read(&path).unwrap_or_else(|err| panic!("failed to read {}: {}", path, err));
+    serde_json::from_slice(&data)
+        .unwrap_or_else(|err| panic!("invalid policies in {}: {}", path, err))
+}
+
+/// Builds the attributes the conditions are evaluated over.
+pub fn attributes(subject: Value, resource: Value, context: Value) -> Value {
+    json!({
+        "subject": subject,
+        "resource": resource,
+        "context": context,
+    })
+}
+
+/// Attributes of a user: its fields and the names of its groups.
+pub fn user(user: &UserWithGroups) -> Value {
+    let mut value = json!(user.user);
+    value["groups"] = json!(user
+        .all_groups()
+        .map(|group| &group.name)
+        .collect::<Vec<_>>());
+    value
+}
+
+/// Attributes of the caller, those of `user` with its permissions.
+pub fn subject(caller: &UserWithGroups, permissions: &[String]) -> Value {
+    let mut subject = user(caller);
+    subject["permissions"] = json!(permissions);
+    subject
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_GRANTS: &[&str] = &[];
+
+    fn rule(effect: Effect, permission: &str, conditions: Value) -> Rule {
+        Rule {
+            name: String::from("test"),
+            effect,
+            permission: String::from(permission),
+            conditions: serde_json::from_value(conditions).unwrap(),
+        }
+    }
+
+    fn department_rule() -> Rule {
+        rule(
+            Effect::Allow,
+            "user:read",
+            json!([{
+                "left": { "attr": "resource.attributes.department" },
+                "op": "eq",
+                "right": { "attr": "subject.attributes.department" }
+            }]),
+        )
+    }
+
+    #[test]
+    fn allows_when_conditions_hold() {
+        let engine = PolicyEngine::new(vec![department_rule()]);
+        let attributes = attributes(
+            json!({ "attributes": { "department": "sales" } }),
+            json!({ "attributes": { "department": "sales" } }),
+            json!({}),
+        );
+        assert_eq!(engine.evaluate("user:read", &attributes), Decision::Allow);
+        assert!(engine.check("user:read", NO_GRANTS, false, &attributes));
+    }
+
+    #[test]
+    fn not_applicable_when_conditions_fail() {
+        let engine = PolicyEngine::new(vec![department_rule()]);
+        let attributes = attributes(
+            json!({ "attributes": { "department": "sales" } }),
+            json!({ "attributes": { "department": "support" } }),
+            json!({}),
+        );
+        assert_eq!(
+            engine.evaluate("user:read", &attributes),
+            Decision::NotApplicable
+        );
+        assert!(!engine.check("user:read", NO_GRANTS, false, &attributes));
+        assert!(engine.check("user:read", NO_GRANTS, true, &attributes));
+    }
+
+    #[test]
+    fn missing_attributes_never_match() {
+        let engine = PolicyEngine::new(vec![department_rule()]);
+        let attributes = attributes(json!({}), json!({}), json!({}));
+        assert_eq!(
+            engine.evaluate("user:read", &attributes),
+            Decision::NotApplicable
+        );
+    }
+
+    #[test]
+    fn deny_wins() {
+        let engine = PolicyEngine::new(vec![
+            rule(Effect::Allow, "user:*", json!([])),
+            rule(
+                Effect::Deny,
+                "*:update",
+                json!([{ "left": { "attr": "context.hour" }, "op": "lt", "right": 9 }]),
+            ),
+        ]);
+        let night = attributes(json!({}), json!({}), json!({ "hour": 3 }));
+        let day = attributes(json!({}), json!({}), json!({ "hour": 10 }));
+        assert_eq!(engine.evaluate("user:update", &night), Decision::Deny);
+        assert!(!engine.check("user:update", NO_GRANTS, true, &night));
+        assert_eq!(engine.evaluate("user:update", &day), Decision::Allow);
+        assert_eq!(engine.evaluate("user:read", &night), Decision::Allow);
+    }
+
+    #[test]
+    fn allow_rules_keep_deny_entries() {
+        let engine = PolicyEngine::new(vec![department_rule()]);
+        let attributes = attributes(
+            json!({ "attributes": { "department": "sales" } }),
+            json!({ "attributes": { "department": "sales" } }),
+            json!({}),
+        );
+        assert!(!engine.check("user:read", &["!user:read"], false, &attributes));
+        assert!(!engine.check("user:read", &["user:*", "!user:*"], false, &attributes));
+        assert!(engine.check("user:read", &["root", "!user:*"], true, &attributes));
+        assert!(engine.check("user:read", &["!user:update"], false, &attributes));
+    }
+
+    #[test]
+    fn deciding_rule_is_the_one_check_follows() {
+        let engine = PolicyEngine::new(vec![
+            department_rule(),
+            rule(
+                Effect::Deny,
+                "user:update",
+                json!([{ "left": { "attr": "context.hour" }, "op": "lt", "right": 6 }]),
+            ),
+        ]);
+        let attributes = attributes(
+            json!({ "attributes": { "department": "sales" } }),
+            json!({ "attributes": { "department": "sales" } }),
+            json!({ "hour": 3 }),
+        );
+        let rule = engine.deciding_rule("user:read", NO_GRANTS, &attributes);
+        assert_eq!(rule.map(|rule| rule.effect), Some(Effect::Allow));
+        assert!(engine
+            .deciding_rule("user:read", &["!user:read"], &attributes)
+            .is_none());
+        let rule = engine.deciding_rule("user:update", &["!user:update"], &attributes);
+        assert_eq!(rule.map(|rule| rule.effect), Some(Effect::Deny));
+    }
+
+    #[test]
+    fn applies_to_matching_permissions() {
+        let engine = PolicyEngine::new(vec![department_rule()]);
+        assert!(engine.applies_to("user:read"));
+        assert!(!engine.applies_to("user:update"));
+        assert!(!PolicyEngine::new(vec![]).applies_to("user:read"));
+    }
+
+    #[test]
+    fn operators() {
+        let attributes = attributes(
+            json!({ "groups": ["support", "sales"], "role": "agent" }),
+            json!({}),
+            json!({ "time": "10:30", "weekday": 6 }),
+        );
+        let holds = |left: Value, op: &str, right: Value| {
+            let condition: Condition =
+                serde_json::from_value(json!({ "left": left, "op": op, "right": right })).unwrap();
+            condition.holds(&attributes)
+        };
+        assert!(holds(
+            json!({ "attr": "subject.role" }),
+            "eq",
+            json!("agent")
+        ));
+        assert!(holds(
+            json!({ "attr": "subject.role" }),
+            "ne",
+            json!("admin")
+        ));
+        assert!(holds(
+            json!({ "attr": "subject.role" }),
+            "in",
+            json!(["agent", "lead"])
+        ));
+        assert!(!holds(
+            json!({ "attr": "subject.role" }),
+            "in",
+            json!("agent")
+        ));
+        assert!(holds(
+            json!({ "attr": "subject.groups" }),
+            "contains",
+            json!("support")
+        ));
+        assert!(!holds(
+            json!({ "attr": "subject.groups" }),
+            "contains",
+            json!("admin")
+        ));
+        assert!(holds(
+            json!({ "attr": "context.time" }),
+            "gte",
+            json!("09:00")
+        ));
+        assert!(holds(
+            json!({ "attr": "context.time" }),
+            "lt",
+            json!("18:00")
+        ));
+        assert!(holds(json!({ "attr": "context.weekday" }), "gt", json!(5)));
+        assert!(holds(json!({ "attr": "context.weekday" }), "lte", json!(6)));
+        assert!(!holds(
+            json!({ "attr": "context.weekday" }),
+            "lt",
+            json!("7")
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+//! Permissions given on single resources with grants, they are checked on
+//! each request instead of being added to the tokens.
+
+use uuid::Uuid;
+
+use crate::repository::GrantRepository;
+
+use super::{permission, Jwt};
+
+/// Returns true if the caller has `permission` on the resource, from its
+/// token or from a grant on the resource. Deny entries of the token still
+/// apply to the grants.
+pub async fn allows(
+    jwt: &Jwt,
+    grants: &GrantRepository,
+    resource_type: &str,
+    resource_id: Uuid,
+    permission: &str,
+) -> Result<bool, sqlx::Error> {
+    check(jwt, grants, resource_type, resource_id, permission)
+        .await
+        .map(|(allowed, _)| allowed)
+}
+
+/// Same as `allows`, also telling why.
+pub async fn check(
+    jwt: &Jwt,
+    grants: &GrantRepository,
+    resource_type: &str,
+    resource_id: Uuid,
+    permission: &str,
+) -> Result<(bool, String), sqlx::Error> {
+    let reason = permission::check(&jwt.perms, permission);
+    if reason.allowed() {
+        return Ok((true, reason.to_string()));
+    }
+    let mut scoped = grants
+        .permissions(jwt.id, resource_type, resource_id)
+        .await?;
+    if scoped.is_empty() {
+        return Ok((false, reason.to_string()));
+    }
+    scoped.extend(
+        jwt.perms
+            .iter()
+            .filter(|p| p.starts_with(permission::DENY_PREFIX))
+            .cloned(),
+    );
+    let reason = match permission::check(&scoped, permission) {
+        permission::Reason::Granted(grant) => (
+            true,
+            format!("granted by {} on {} {}", grant, resource_type, resource_id),
+        ),
+        reason => (reason.allowed(), reason.to_string()),
+    };
+    Ok(reason)
+}
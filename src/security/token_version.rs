@@ -0,0 +1,52 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+/// Token versions read by the `Jwt` extractor, kept for
+/// `TOKEN_VERSION_CACHE_TTL` seconds (30 by default). Entries are dropped as
+/// soon as this instance bumps a version, other instances see the change once
+/// their entry expires.
+static CACHE: LazyLock<RwLock<HashMap<Uuid, (i32, Instant)>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+static TTL: LazyLock<Duration> = LazyLock::new(|| {
+    let secs = std::env::var("TOKEN_VERSION_CACHE_TTL")
+        .map(|ttl| {
+            ttl.parse()
+                .expect("TOKEN_VERSION_CACHE_TTL must be a number")
+        })
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+});
+
+/// Reads the cache lifetime, so an invalid value stops the server when it
+/// starts.
+pub fn init() {
+    LazyLock::force(&TTL);
+}
+
+pub fn get(user_id: Uuid) -> Option<i32> {
+    let cache = CACHE.read().unwrap();
+    match cache.get(&user_id) {
+        Some((version, cached_at)) if cached_at.elapsed() < *TTL => Some(*version),
+        _ => None,
+    }
+}
+
+pub fn set(user_id: Uuid, version: i32) {
+    let mut cache = CACHE.write().unwrap();
+    cache.retain(|_, (_, cached_at)| cached_at.elapsed() < *TTL);
+    cache.insert(user_id, (version, Instant::now()));
+}
+
+pub fn invalidate(user_id: Uuid) {
+    CACHE.write().unwrap().remove(&user_id);
+}
+
+pub fn clear() {
+    CACHE.write().unwrap().clear();
+}
@@ -0,0 +1,98 @@
+use std::sync::LazyLock;
+
+use chrono::Utc;
+use rand::RngCore;
+
+use super::permission;
+
+/// What unverified email addresses prevent, set with `EMAIL_VERIFICATION_POLICY`.
+pub enum EmailPolicy {
+    /// Unverified users are treated like verified ones (default).
+    None,
+    /// Unverified users can not log in.
+    Login,
+    /// The permissions listed in `EMAIL_UNVERIFIED_DENIED_PERMISSIONS` are
+    /// denied in the tokens of unverified users.
+    Permissions(Vec<String>),
+}
+
+static POLICY: LazyLock<EmailPolicy> = LazyLock::new(EmailPolicy::from_env);
+
+/// Lifetime in seconds of the verification tokens.
+static TTL: LazyLock<i64> = LazyLock::new(|| {
+    std::env::var("EMAIL_VERIFICATION_TTL")
+        .map(|ttl| {
+            ttl.parse()
+                .expect("EMAIL_VERIFICATION_TTL must be a number")
+        })
+        .unwrap_or(60 * 60 * 24)
+});
+
+/// Reads the email verification settings, so an invalid value stops the
+/// server when it starts.
+pub fn init() {
+    LazyLock::force(&POLICY);
+    LazyLock::force(&TTL);
+}
+
+/// The policy set with `EMAIL_VERIFICATION_POLICY`.
+pub fn policy() -> &'static EmailPolicy {
+    &POLICY
+}
+
+impl EmailPolicy {
+    fn from_env() -> Self {
+        let policy = std::env::var("EMAIL_VERIFICATION_POLICY").unwrap_or(String::from("none"));
+        match policy.as_str() {
+            "none" => EmailPolicy::None,
+            "login" => EmailPolicy::Login,
+            "permissions" => {
+                let list = std::env::var("EMAIL_UNVERIFIED_DENIED_PERMISSIONS").unwrap_or_default();
+                EmailPolicy::Permissions(
+                    list.split(',')
+                        .map(|p| p.trim())
+                        .filter(|p| !p.is_empty())
+                        .map(String::from)
+                        .collect(),
+                )
+            }
+            _ => panic!("unknown EMAIL_VERIFICATION_POLICY: {}", policy),
+        }
+    }
+
+    pub fn blocks_login(&self) -> bool {
+        matches!(self, EmailPolicy::Login)
+    }
+
+    /// Deny entries added to the tokens of unverified users.
+    pub fn deny_entries(&self) -> Vec<String> {
+        match self {
+            EmailPolicy::Permissions(list) => list
+                .iter()
+                .map(|p| format!("{}{}", permission::DENY_PREFIX, p))
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Generates a random url safe token.
+pub fn token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Returns the expiration of an email verification token, the lifetime in
+/// seconds is set with `EMAIL_VERIFICATION_TTL` and defaults to one day.
+pub fn expires_at() -> i64 {
+    Utc::now().timestamp() + *TTL
+}
+
+/// Returns the link sent to the user, `EMAIL_VERIFICATION_URL` should point
+/// to a page that calls `POST /verify-email` or to `GET /verify-email` itself.
+pub fn link(token: &str) -> String {
+    let url = std::env::var("EMAIL_VERIFICATION_URL")
+        .unwrap_or(String::from("http://localhost:4000/verify-email"));
+    format!("{}?token={}", url, token)
+}
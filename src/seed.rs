@@ -0,0 +1,133 @@
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    model::{GroupDto, PermissionDto, UserCreateDto, SYSTEM_ORGANIZATION},
+    repository::{GroupRepository, PermissionRepository, UserRepository},
+    security,
+};
+
+/// Registers the permissions of this service and, on the first start, creates
+/// the `root`, `admin` and `nobody` groups with their users.
+pub async fn run(db: Pool<Postgres>) {
+    register_permissions(db.clone()).await;
+    init_system(db).await;
+}
+
+async fn register_permissions(db: Pool<Postgres>) {
+    let repo = PermissionRepository::new(db);
+    let permissions = security::permission::PERMISSIONS
+        .iter()
+        .map(|(name, description)| PermissionDto {
+            name: String::from(*name),
+            description: Some(String::from(*description)),
+        })
+        .collect();
+    repo.register(security::permission::SERVICE, permissions)
+        .await
+        .expect("failed to register permissions");
+}
+
+async fn init_system(db: Pool<Postgres>) {
+    let repo = GroupRepository::new(db.clone());
+    let urepo = UserRepository::new(db);
+    if repo.count().await.unwrap() > 0 {
+        return;
+    }
+    let dto = GroupDto {
+        name: String::from("root"),
+        description: Some(String::from("super users group")),
+        permissions: vec![String::from("root")],
+        visible: Some(false),
+        editable: Some(false),
+        locked: Some(true),
+        require_mfa: Some(true),
+        allow_passwordless: None,
+        idle_timeout: None,
+        max_sessions: None,
+    };
+    match repo.create(SYSTEM_ORGANIZATION, dto).await {
+        Ok(group) => {
+            let hash = security::password::hash("root").expect("failed to hash password");
+            let dto = UserCreateDto {
+                name: String::from("root"),
+                phone: None,
+                role: None,
+                email: String::from("root@change.me"),
+                username: String::from("root"),
+                password: String::from("root"),
+                password_hash: hash,
+                visible: false,
+                editable: false,
+                locked: false,
+                attributes: None,
+                groups: vec![group.id],
+                // nobody can receive mail at the seeded addresses
+                email_verified: true,
+                registered: false,
+            };
+            if let Err(e) = urepo.create(SYSTEM_ORGANIZATION, dto).await {
+                panic!("failed to create root user: {}", e);
+            }
+        }
+        Err(e) => {
+            panic!("failed to create root group: {}", e);
+        }
+    }
+
+    let dto = GroupDto {
+        name: String::from("admin"),
+        description: Some(String::from("admin users group")),
+        permissions: vec![String::from("admin")],
+        visible: Some(true),
+        editable: Some(false),
+        locked: Some(true),
+        require_mfa: Some(true),
+        allow_passwordless: None,
+        idle_timeout: None,
+        max_sessions: None,
+    };
+    match repo.create(SYSTEM_ORGANIZATION, dto).await {
+        Ok(group) => {
+            let hash = security::password::hash("admin").expect("failed to hash password");
+            let dto = UserCreateDto {
+                name: String::from("admin"),
+                phone: None,
+                role: None,
+                email: String::from("admin@change.me"),
+                username: String::from("admin"),
+                password: String::from("admin"),
+                password_hash: hash,
+                visible: true,
+                editable: false,
+                locked: false,
+                attributes: None,
+                groups: vec![group.id],
+                // nobody can receive mail at the seeded addresses
+                email_verified: true,
+                registered: false,
+            };
+            if let Err(e) = urepo.create(SYSTEM_ORGANIZATION, dto).await {
+                panic!("failed to create admin user: {}", e);
+            }
+        }
+        Err(e) => {
+            panic!("failed to create admin group: {}", e);
+        }
+    }
+
+    let dto = GroupDto {
+        name: String::from("nobody"),
+        description: Some(String::from("nobody users group")),
+        permissions: vec![String::from("nobody")],
+        visible: Some(false),
+        editable: Some(false),
+        locked: Some(true),
+        require_mfa: None,
+        allow_passwordless: None,
+        idle_timeout: None,
+        max_sessions: None,
+    };
+    if let Err(e) = repo.create(SYSTEM_ORGANIZATION, dto).await {
+        panic!("failed to create nobody group: {}", e);
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+use sqlx::{Pool, Postgres};
+
+use crate::{
+    mail::{self, Mailer},
+    repository::{
+        EmailVerificationRepository, GrantRepository, GroupRepository, LoginTokenRepository,
+        MembershipRequestRepository, MfaRepository, OrganizationRepository, PermissionRepository,
+        SessionRepository, UserRepository,
+    },
+    security::{jwt, policy::PolicyEngine},
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub users: UserRepository,
+    pub groups: GroupRepository,
+    pub grants: GrantRepository,
+    pub permissions: PermissionRepository,
+    pub mfa: MfaRepository,
+    pub organizations: OrganizationRepository,
+    pub email_verifications: EmailVerificationRepository,
+    pub login_tokens: LoginTokenRepository,
+    pub membership_requests: MembershipRequestRepository,
+    pub sessions: SessionRepository,
+    pub mailer: Arc<dyn Mailer>,
+    pub policies: PolicyEngine,
+}
+
+impl AppState {
+    pub fn new(db: Pool<Postgres>, policies: PolicyEngine) -> Self {
+        AppState {
+            users: UserRepository::new(db.clone()),
+            groups: GroupRepository::new(db.clone()),
+            grants: GrantRepository::new(db.clone()),
+            permissions: PermissionRepository::new(db.clone()),
+            mfa: MfaRepository::new(db.clone()),
+            organizations: OrganizationRepository::new(db.clone()),
+            email_verifications: EmailVerificationRepository::new(db.clone()),
+            login_tokens: LoginTokenRepository::new(db.clone()),
+            membership_requests: MembershipRequestRepository::new(db.clone()),
+            sessions: SessionRepository::new(db, jwt::TOKEN_TTL),
+            mailer: mail::from_env(),
+            policies,
+        }
+    }
+}
+
+impl FromRef<AppState> for UserRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.users.clone()
+    }
+}
+
+impl FromRef<AppState> for GroupRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.groups.clone()
+    }
+}
+
+impl FromRef<AppState> for GrantRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.grants.clone()
+    }
+}
+
+impl FromRef<AppState> for PermissionRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.permissions.clone()
+    }
+}
+
+impl FromRef<AppState> for MfaRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.mfa.clone()
+    }
+}
+
+impl FromRef<AppState> for OrganizationRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.organizations.clone()
+    }
+}
+
+impl FromRef<AppState> for EmailVerificationRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.email_verifications.clone()
+    }
+}
+
+impl FromRef<AppState> for LoginTokenRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.login_tokens.clone()
+    }
+}
+
+impl FromRef<AppState> for MembershipRequestRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.membership_requests.clone()
+    }
+}
+
+impl FromRef<AppState> for SessionRepository {
+    fn from_ref(state: &AppState) -> Self {
+        state.sessions.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<dyn Mailer> {
+    fn from_ref(state: &AppState) -> Self {
+        state.mailer.clone()
+    }
+}
+
+impl FromRef<AppState> for PolicyEngine {
+    fn from_ref(state: &AppState) -> Self {
+        state.policies.clone()
+    }
+}
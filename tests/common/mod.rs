@@ -0,0 +1,170 @@
+//! Helpers of the integration tests, each test gets its own database from
+//! `#[sqlx::test]`, created on the server in `DATABASE_URL`.
+
+// every test file only uses some of the helpers
+#![allow(dead_code)]
+
+use std::{fs, sync::Once};
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use gaia_auth::{
+    controller,
+    model::{Group, GroupDto, PermissionDto, SessionPolicy, UserCreateDto, SYSTEM_ORGANIZATION},
+    repository::{GroupRepository, PermissionRepository, SessionRepository, UserRepository},
+    security::{jwt, password, policy::PolicyEngine, ClientInfo},
+    seed,
+    state::AppState,
+};
+use rsa::{pkcs1::EncodeRsaPrivateKey, pkcs8::EncodePublicKey, pkcs8::LineEnding, RsaPrivateKey};
+use serde_json::Value;
+use sqlx::PgPool;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+static ENV: Once = Once::new();
+
+/// Sets the configuration of the tests and `vars` before the server reads
+/// it, the first call of a test binary decides.
+pub fn env(vars: &[(&str, &str)]) {
+    ENV.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("gaia-auth-tests-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let private_key = dir.join("private.pem");
+        let public_key = dir.join("public.pem");
+        fs::write(&private_key, key.to_pkcs1_pem(LineEnding::LF).unwrap()).unwrap();
+        let pem = key
+            .to_public_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        fs::write(&public_key, pem).unwrap();
+
+        std::env::set_var("JWT_PRIVATE_KEY", private_key);
+        std::env::set_var("JWT_PUBLIC_KEY", public_key);
+        std::env::set_var(
+            "PASSWORD_SALT",
+            "816bfb5ca97ba33ef2cdd33763624bd34cd3b0d16aba0f94a6228481b009b0b3",
+        );
+        std::env::set_var("MAIL_TRANSPORT", "log");
+        for (key, value) in vars {
+            std::env::set_var(key, value);
+        }
+    });
+}
+
+/// Seeds the database and returns the routes of the server.
+pub async fn app(db: &PgPool) -> Router {
+    env(&[]);
+    seed::run(db.clone()).await;
+    let state = AppState::new(db.clone(), PolicyEngine::new(vec![]));
+    controller::routes().with_state(state)
+}
+
+/// Registers permissions of a service other than gaia.
+pub async fn register(db: &PgPool, names: &[&str]) {
+    let permissions = names
+        .iter()
+        .map(|name| PermissionDto {
+            name: String::from(*name),
+            description: None,
+        })
+        .collect();
+    PermissionRepository::new(db.clone())
+        .register("tests", permissions)
+        .await
+        .unwrap();
+}
+
+/// Creates an editable group of the system organization.
+pub async fn group(db: &PgPool, name: &str, permissions: &[&str]) -> Group {
+    let dto = GroupDto {
+        name: String::from(name),
+        description: None,
+        permissions: permissions.iter().map(|p| String::from(*p)).collect(),
+        visible: Some(true),
+        editable: Some(true),
+        locked: Some(false),
+        require_mfa: None,
+        allow_passwordless: None,
+        idle_timeout: None,
+        max_sessions: None,
+    };
+    GroupRepository::new(db.clone())
+        .create(SYSTEM_ORGANIZATION, dto)
+        .await
+        .unwrap()
+}
+
+pub struct TestUser {
+    pub id: Uuid,
+    /// Access token of a session of the user.
+    pub token: String,
+}
+
+/// Creates a verified user of the system organization in `groups`, with
+/// `password` as password and an access token.
+pub async fn user(db: &PgPool, username: &str, groups: &[Uuid]) -> TestUser {
+    let repo = UserRepository::new(db.clone());
+    let dto = UserCreateDto {
+        name: String::from(username),
+        phone: None,
+        role: None,
+        email: format!("{}@example.com", username),
+        username: String::from(username),
+        password: String::from("password"),
+        password_hash: password::hash("password").unwrap(),
+        visible: true,
+        editable: true,
+        locked: false,
+        attributes: None,
+        groups: groups.to_vec(),
+        email_verified: true,
+        registered: false,
+    };
+    let user = repo.create(SYSTEM_ORGANIZATION, dto).await.unwrap();
+    let session = SessionRepository::new(db.clone(), jwt::TOKEN_TTL)
+        .create(
+            user.user.id,
+            &ClientInfo::default(),
+            &SessionPolicy::default(),
+        )
+        .await
+        .unwrap();
+    let user = repo.find_with_groups(user.user.id).await.unwrap();
+    TestUser {
+        id: user.user.id,
+        token: jwt::generate_token(&user, session.id)
+            .unwrap_or_else(|(_, err)| panic!("{}", err.error)),
+    }
+}
+
+/// Sends a request to the server, the body of the response is `Null` when
+/// it is empty.
+pub async fn send(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let request = match body {
+        Some(body) => request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, body)
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use sqlx::PgPool;
+
+fn login(username: &str, password: &str) -> Option<serde_json::Value> {
+    Some(json!({ "username": username, "password": password }))
+}
+
+#[sqlx::test]
+async fn login_policy_lets_seeded_users_in(db: PgPool) {
+    common::env(&[("EMAIL_VERIFICATION_POLICY", "login")]);
+    let app = common::app(&db).await;
+
+    // the seeded groups require MFA, the first login asks for an enrollment
+    for name in ["root", "admin"] {
+        let (status, body) =
+            common::send(&app, Method::POST, "/login", None, login(name, name)).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", name, body);
+        assert_eq!(body["mfa_enrollment_required"], true);
+    }
+}
+
+#[sqlx::test]
+async fn login_policy_refuses_unverified_users(db: PgPool) {
+    common::env(&[("EMAIL_VERIFICATION_POLICY", "login")]);
+    let app = common::app(&db).await;
+    let group = common::group(&db, "staff", &["user:read"]).await;
+    let user = common::user(&db, "alice", &[group.id]).await;
+
+    let (status, body) = common::send(
+        &app,
+        Method::POST,
+        "/login",
+        None,
+        login("alice", "password"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+
+    sqlx::query("update users set email_verified_at = null where id = $1")
+        .bind(user.id)
+        .execute(&db)
+        .await
+        .unwrap();
+    let (status, body) = common::send(
+        &app,
+        Method::POST,
+        "/login",
+        None,
+        login("alice", "password"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "email_not_verified");
+}
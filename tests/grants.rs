@@ -0,0 +1,143 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use gaia_auth::{
+    model::{GrantDto, GROUP_RESOURCE, SYSTEM_ORGANIZATION},
+    repository::{GrantRepository, GroupRepository, UserRepository},
+    security::{scope, Jwt},
+};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn grant(db: &PgPool, group_id: Uuid, user_id: Option<Uuid>, to: Option<Uuid>, name: &str) {
+    let dto = GrantDto {
+        user_id,
+        group_id: to,
+        permission: String::from(name),
+    };
+    GrantRepository::new(db.clone())
+        .create(SYSTEM_ORGANIZATION, GROUP_RESOURCE, group_id, dto)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn scopes_resolve_grants_of_the_user_and_its_groups(db: PgPool) {
+    common::env(&[]);
+    let department = common::group(&db, "department", &[]).await;
+    let team = common::group(&db, "team", &["!group:delete"]).await;
+    GroupRepository::new(db.clone())
+        .add_member_group(department.id, team.id)
+        .await
+        .unwrap();
+    let bob = common::user(&db, "bob", &[team.id]).await;
+    let reports = common::group(&db, "reports", &[]).await;
+    let other = common::group(&db, "other", &[]).await;
+    grant(&db, reports.id, Some(bob.id), None, "group:update").await;
+    grant(&db, reports.id, None, Some(department.id), "group:read").await;
+    grant(&db, reports.id, Some(bob.id), None, "group:delete").await;
+
+    let user = UserRepository::new(db.clone())
+        .find_with_groups(bob.id)
+        .await
+        .unwrap();
+    let jwt = Jwt::for_user(&user);
+    let grants = GrantRepository::new(db.clone());
+    let allows = |id, permission| scope::allows(&jwt, &grants, GROUP_RESOURCE, id, permission);
+    assert!(allows(reports.id, "group:update").await.unwrap());
+    // through the group containing the group of the user
+    assert!(allows(reports.id, "group:read").await.unwrap());
+    // deny entries of the token apply to the grants
+    assert!(!allows(reports.id, "group:delete").await.unwrap());
+    assert!(!allows(reports.id, "group:create").await.unwrap());
+    assert!(!allows(other.id, "group:update").await.unwrap());
+
+    let (allowed, reason) = scope::check(&jwt, &grants, GROUP_RESOURCE, reports.id, "group:update")
+        .await
+        .unwrap();
+    assert!(allowed);
+    assert_eq!(
+        reason,
+        format!("granted by group:update on group {}", reports.id)
+    );
+}
+
+#[sqlx::test]
+async fn scoped_updates_only_grant_what_the_caller_could(db: PgPool) {
+    let app = common::app(&db).await;
+    common::register(&db, &["report:read", "billing:write"]).await;
+    let staff = common::group(&db, "staff", &["report:read"]).await;
+    let bob = common::user(&db, "bob", &[staff.id]).await;
+    let reports = common::group(&db, "reports", &["!billing:write"]).await;
+    let other = common::group(&db, "other", &[]).await;
+    grant(&db, reports.id, Some(bob.id), None, "group:update").await;
+    let token = Some(bob.token.as_str());
+    let uri = format!("/groups/{}", reports.id);
+
+    let body = json!({ "name": "renamed", "permissions": ["!billing:write"] });
+    let (status, body) = common::send(&app, Method::PUT, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body = json!({ "name": "renamed", "permissions": ["!billing:write", "report:read"] });
+    let (status, _) = common::send(&app, Method::PUT, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // permissions the caller does not have, also by dropping a deny entry
+    let body = json!({ "name": "renamed", "permissions": ["!billing:write", "billing:write"] });
+    let (status, _) = common::send(&app, Method::PUT, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let body = json!({ "name": "renamed", "permissions": ["report:read"] });
+    let (status, _) = common::send(&app, Method::PUT, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let body = json!({ "name": "renamed", "permissions": ["!billing:write", "root"] });
+    let (status, _) = common::send(&app, Method::PUT, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let group = GroupRepository::new(db.clone())
+        .find_by_id(SYSTEM_ORGANIZATION, reports.id)
+        .await
+        .unwrap();
+    assert_eq!(group.permissions.0, ["!billing:write", "report:read"]);
+
+    // nor by joining a group with them
+    let carol = common::user(&db, "carol", &[]).await;
+    let uri = format!("/groups/{}/members", reports.id);
+    let body = json!({ "user_id": carol.id });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let billing = common::group(&db, "billing", &["billing:write"]).await;
+    grant(&db, billing.id, Some(bob.id), None, "group:update").await;
+    let uri = format!("/groups/{}/members", billing.id);
+    let body = json!({ "user_id": bob.id });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let uri = format!("/groups/{}/groups/{}", billing.id, staff.id);
+    let (status, _) = common::send(&app, Method::PUT, &uri, token, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // the grant only covers its group
+    let uri = format!("/groups/{}", other.id);
+    let body = json!({ "name": "other", "permissions": [] });
+    let (status, _) = common::send(&app, Method::PUT, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn scoped_grants_are_only_passed_on_when_held(db: PgPool) {
+    let app = common::app(&db).await;
+    let bob = common::user(&db, "bob", &[]).await;
+    let carol = common::user(&db, "carol", &[]).await;
+    let reports = common::group(&db, "reports", &[]).await;
+    grant(&db, reports.id, Some(bob.id), None, "group:update").await;
+    let token = Some(bob.token.as_str());
+    let uri = format!("/groups/{}/access", reports.id);
+
+    let body = json!({ "user_id": carol.id, "permission": "group:update" });
+    let (status, body) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let body = json!({ "user_id": carol.id, "permission": "group:delete" });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let body = json!({ "user_id": carol.id, "permission": "user:read" });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+}
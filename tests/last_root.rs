@@ -0,0 +1,93 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use gaia_auth::{
+    model::SYSTEM_ORGANIZATION,
+    repository::{GroupRepository, UserRepository},
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+/// Locks the seeded root user, the users created by the test are the only
+/// active root users.
+async fn lock_seeded_root(db: &PgPool) {
+    sqlx::query("update users set locked = true where username = 'root'")
+        .execute(db)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test]
+async fn changes_leaving_no_root_user_are_refused(db: PgPool) {
+    let app = common::app(&db).await;
+    lock_seeded_root(&db).await;
+    let outer = common::group(&db, "outer", &["root"]).await;
+    let admins = common::group(&db, "admins", &[]).await;
+    GroupRepository::new(db.clone())
+        .add_member_group(outer.id, admins.id)
+        .await
+        .unwrap();
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    let token = Some(admin.token.as_str());
+    let uri = format!("/groups/{}/groups/{}", outer.id, admins.id);
+
+    let requests = [
+        (Method::DELETE, uri.clone(), None),
+        (Method::DELETE, format!("/groups/{}", outer.id), None),
+        (
+            Method::PUT,
+            format!("/groups/{}", outer.id),
+            Some(json!({ "name": "outer", "permissions": [] })),
+        ),
+        (
+            Method::DELETE,
+            format!("/groups/{}/members/{}", admins.id, admin.id),
+            None,
+        ),
+        (
+            Method::PATCH,
+            format!("/groups/{}/members", admins.id),
+            Some(json!({ "add": [], "remove": [admin.id] })),
+        ),
+        (
+            Method::PUT,
+            format!("/users/{}", admin.id),
+            Some(json!({
+                "name": "alice",
+                "email": "alice@example.com",
+                "username": "alice",
+                "visible": true,
+                "editable": true,
+                "locked": false,
+                "groups": [],
+            })),
+        ),
+        (Method::DELETE, format!("/users/{}", admin.id), None),
+    ];
+    for (method, uri, body) in requests {
+        let (status, body) = common::send(&app, method.clone(), &uri, token, body).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{} {}: {}", method, uri, body);
+        assert_eq!(body["code"], "last_root");
+    }
+
+    // nothing changed, the token still has root
+    let uri = format!("/users/{}", admin.id);
+    let (status, body) = common::send(&app, Method::GET, &uri, token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["groups"][0]["id"], admins.id.to_string());
+}
+
+#[sqlx::test]
+async fn concurrent_deletes_keep_a_root_user(db: PgPool) {
+    common::env(&[]);
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let alice = common::user(&db, "alice", &[admins.id]).await;
+    let carol = common::user(&db, "carol", &[admins.id]).await;
+    let users = UserRepository::new(db.clone());
+
+    let (first, second) = tokio::join!(
+        users.delete(SYSTEM_ORGANIZATION, alice.id),
+        users.delete(SYSTEM_ORGANIZATION, carol.id),
+    );
+    assert!(first.unwrap() != second.unwrap());
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use gaia_auth::repository::LoginTokenRepository;
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn failed_code_attempts_are_counted_across_codes(db: PgPool) {
+    common::env(&[]);
+    let bob = common::user(&db, "bob", &[]).await;
+    let tokens = LoginTokenRepository::new(db.clone());
+    let expires_at = chrono::Utc::now().timestamp() + 600;
+
+    tokens
+        .create(bob.id, "code", b"first".to_vec(), expires_at)
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        assert!(!tokens
+            .consume_code(bob.id, b"wrong".to_vec())
+            .await
+            .unwrap());
+    }
+    // a new code does not reset the attempts
+    tokens
+        .create(bob.id, "code", b"second".to_vec(), expires_at)
+        .await
+        .unwrap();
+    assert!(!tokens
+        .consume_code(bob.id, b"first".to_vec())
+        .await
+        .unwrap());
+    assert!(!tokens.code_attempts_spent(bob.id).await.unwrap());
+    assert!(!tokens
+        .consume_code(bob.id, b"wrong".to_vec())
+        .await
+        .unwrap());
+    assert!(tokens.code_attempts_spent(bob.id).await.unwrap());
+    assert!(!tokens
+        .consume_code(bob.id, b"second".to_vec())
+        .await
+        .unwrap());
+
+    // the attempts of other users do not count
+    let other = common::user(&db, "carol", &[]).await;
+    tokens
+        .create(other.id, "code", b"code".to_vec(), expires_at)
+        .await
+        .unwrap();
+    assert!(tokens
+        .consume_code(other.id, b"code".to_vec())
+        .await
+        .unwrap());
+}
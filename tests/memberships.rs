@@ -0,0 +1,117 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+async fn validity(db: &PgPool, user_id: Uuid, group_id: Uuid) -> (Option<i64>, Option<i64>) {
+    let sql =
+        "select valid_from, valid_until from users_groups where user_id = $1 and group_id = $2";
+    sqlx::query_as(sql)
+        .bind(user_id)
+        .bind(group_id)
+        .fetch_one(db)
+        .await
+        .unwrap()
+}
+
+#[sqlx::test]
+async fn adding_a_member_does_not_shorten_a_membership(db: PgPool) {
+    let app = common::app(&db).await;
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    let group = common::group(&db, "reports", &["user:read"]).await;
+    let member = common::user(&db, "bob", &[group.id]).await;
+    let uri = format!("/groups/{}/members", group.id);
+    let token = Some(admin.token.as_str());
+    let until = now() + 3600;
+
+    let body = json!({ "user_id": member.id, "valid_until": until });
+    let (status, body) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert_eq!(validity(&db, member.id, group.id).await, (None, None));
+
+    let temporary = common::user(&db, "carol", &[]).await;
+    let body = json!({ "user_id": temporary.id, "valid_until": until });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let body = json!({ "user_id": temporary.id, "valid_until": until - 60 });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        validity(&db, temporary.id, group.id).await,
+        (None, Some(until))
+    );
+
+    // extending a membership or making it permanent is allowed
+    let body = json!({ "user_id": temporary.id, "valid_until": until + 60 });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        validity(&db, temporary.id, group.id).await,
+        (None, Some(until + 60))
+    );
+    let body = json!({ "user_id": temporary.id });
+    let (status, _) = common::send(&app, Method::POST, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(validity(&db, temporary.id, group.id).await, (None, None));
+}
+
+#[sqlx::test]
+async fn expired_memberships_are_replaced(db: PgPool) {
+    let app = common::app(&db).await;
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    let group = common::group(&db, "reports", &["user:read"]).await;
+    let member = common::user(&db, "bob", &[group.id]).await;
+    sqlx::query("update users_groups set valid_until = $1 where user_id = $2")
+        .bind(now() - 60)
+        .bind(member.id)
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let until = now() + 3600;
+    let uri = format!("/groups/{}/members", group.id);
+    let body = json!({ "user_id": member.id, "valid_until": until });
+    let (status, _) = common::send(&app, Method::POST, &uri, Some(&admin.token), Some(body)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        validity(&db, member.id, group.id).await,
+        (None, Some(until))
+    );
+}
+
+#[sqlx::test]
+async fn approving_a_request_does_not_shorten_a_membership(db: PgPool) {
+    let app = common::app(&db).await;
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    let group = common::group(&db, "reports", &["user:read"]).await;
+    let member = common::user(&db, "bob", &[group.id]).await;
+
+    let uri = format!("/groups/{}/requests", group.id);
+    let body = json!({ "valid_until": now() + 3600 });
+    let (status, request) =
+        common::send(&app, Method::POST, &uri, Some(&member.token), Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", request);
+
+    let uri = format!(
+        "/groups/{}/requests/{}/approve",
+        group.id,
+        request["id"].as_str().unwrap()
+    );
+    let (status, _) = common::send(&app, Method::POST, &uri, Some(&admin.token), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(validity(&db, member.id, group.id).await, (None, None));
+    let status: String = sqlx::query_scalar("select status from membership_requests")
+        .fetch_one(&db)
+        .await
+        .unwrap();
+    assert_eq!(status, "pending");
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use gaia_auth::{
+    model::SYSTEM_ORGANIZATION,
+    repository::{GroupRepository, UserRepository},
+    security::Jwt,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn permissions(db: &PgPool, id: Uuid) -> Vec<String> {
+    let user = UserRepository::new(db.clone())
+        .find_with_groups(id)
+        .await
+        .unwrap();
+    Jwt::for_user(&user).perms
+}
+
+#[sqlx::test]
+async fn groups_can_not_contain_each_other(db: PgPool) {
+    let app = common::app(&db).await;
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    let company = common::group(&db, "company", &[]).await;
+    let department = common::group(&db, "department", &[]).await;
+    let team = common::group(&db, "team", &[]).await;
+    let token = Some(admin.token.as_str());
+    let nest = |id: Uuid, member_id: Uuid| format!("/groups/{}/groups/{}", id, member_id);
+
+    for (id, member_id) in [(company.id, department.id), (department.id, team.id)] {
+        let uri = nest(id, member_id);
+        let (status, _) = common::send(&app, Method::PUT, &uri, token, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+    for (id, member_id) in [
+        (team.id, company.id),
+        (department.id, company.id),
+        (team.id, team.id),
+    ] {
+        let uri = nest(id, member_id);
+        let (status, _) = common::send(&app, Method::PUT, &uri, token, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+    }
+}
+
+#[sqlx::test]
+async fn concurrent_nesting_does_not_create_a_cycle(db: PgPool) {
+    common::env(&[]);
+    let first = common::group(&db, "first", &[]).await;
+    let second = common::group(&db, "second", &[]).await;
+    let groups = GroupRepository::new(db.clone());
+
+    let (a, b) = tokio::join!(
+        groups.add_member_group(first.id, second.id),
+        groups.add_member_group(second.id, first.id),
+    );
+    assert!(a.unwrap() != b.unwrap());
+}
+
+#[sqlx::test]
+async fn members_inherit_the_permissions_of_containing_groups(db: PgPool) {
+    common::env(&[]);
+    common::register(&db, &["report:read", "report:write"]).await;
+    let company = common::group(&db, "company", &["report:read"]).await;
+    let department = common::group(&db, "department", &["report:write"]).await;
+    let team = common::group(&db, "team", &[]).await;
+    let bob = common::user(&db, "bob", &[team.id]).await;
+    let groups = GroupRepository::new(db.clone());
+    assert!(groups
+        .add_member_group(company.id, department.id)
+        .await
+        .unwrap());
+    assert!(groups
+        .add_member_group(department.id, team.id)
+        .await
+        .unwrap());
+
+    let perms = permissions(&db, bob.id).await;
+    assert!(perms.contains(&String::from("report:read")));
+    assert!(perms.contains(&String::from("report:write")));
+    let user = UserRepository::new(db.clone())
+        .find_with_groups(bob.id)
+        .await
+        .unwrap();
+    let mut inherited: Vec<_> = user.inherited_groups.iter().map(|g| g.id).collect();
+    inherited.sort();
+    let mut expected = vec![company.id, department.id];
+    expected.sort();
+    assert_eq!(inherited, expected);
+
+    // a deleted group passes nothing on, not even what contains it
+    groups
+        .delete(SYSTEM_ORGANIZATION, department.id)
+        .await
+        .unwrap();
+    assert!(permissions(&db, bob.id).await.is_empty());
+}
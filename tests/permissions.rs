@@ -0,0 +1,60 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+fn registration(service: &str, name: &str) -> Option<Value> {
+    Some(json!({
+        "service": service,
+        "permissions": [{ "name": name, "description": "Read the reports" }]
+    }))
+}
+
+#[sqlx::test]
+async fn first_service_claims_permissions_without_a_service(db: PgPool) {
+    let app = common::app(&db).await;
+    let group = common::group(&db, "services", &["permission:register"]).await;
+    let caller = common::user(&db, "reports", &[group.id]).await;
+    // what the migration leaves for permissions groups granted before
+    sqlx::query("insert into permissions (name) values ('report:read')")
+        .execute(&db)
+        .await
+        .unwrap();
+    let token = Some(caller.token.as_str());
+
+    let (status, body) = common::send(
+        &app,
+        Method::POST,
+        "/permissions",
+        token,
+        registration("reports", "report:read"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body[0]["service"], "reports");
+
+    let (status, _) = common::send(
+        &app,
+        Method::POST,
+        "/permissions",
+        token,
+        registration("reports", "report:read"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = common::send(
+        &app,
+        Method::POST,
+        "/permissions",
+        token,
+        registration("billing", "report:read"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(
+        body["error"],
+        "permissions registered by another service: report:read"
+    );
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use gaia_auth::{
+    model::{GroupDto, OrganizationDto},
+    repository::{GroupRepository, OrganizationRepository},
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+async fn organization(db: &PgPool, name: &str, allow_registration: Option<bool>) -> Uuid {
+    let dto = OrganizationDto {
+        name: String::from(name),
+        description: None,
+        allow_registration,
+    };
+    let id = OrganizationRepository::new(db.clone())
+        .create(dto)
+        .await
+        .unwrap()
+        .id;
+    let dto = GroupDto {
+        name: String::from("nobody"),
+        description: None,
+        permissions: vec![],
+        visible: None,
+        editable: None,
+        locked: None,
+        require_mfa: None,
+        allow_passwordless: None,
+        idle_timeout: None,
+        max_sessions: None,
+    };
+    GroupRepository::new(db.clone())
+        .create(id, dto)
+        .await
+        .unwrap();
+    id
+}
+
+fn registration(organization: Option<&str>) -> Value {
+    json!({
+        "name": "bob",
+        "email": "bob@example.com",
+        "username": "bob",
+        "password": "correct horse battery staple",
+        "organization": organization,
+    })
+}
+
+#[sqlx::test]
+async fn organizations_must_allow_registration(db: PgPool) {
+    common::env(&[("REGISTRATION_ENABLED", "true")]);
+    let app = common::app(&db).await;
+    organization(&db, "closed", None).await;
+    let open = organization(&db, "open", Some(true)).await;
+
+    let body = Some(registration(Some("closed")));
+    let (status, _) = common::send(&app, Method::POST, "/register", None, body).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let body = Some(registration(Some("open")));
+    let (status, user) = common::send(&app, Method::POST, "/register", None, body).await;
+    assert_eq!(status, StatusCode::OK, "{}", user);
+    assert_eq!(user["org_id"], open.to_string());
+    assert!(user["registered_at"].is_i64());
+
+    // the system organization allows registration
+    let body = Some(registration(None));
+    let (status, user) = common::send(&app, Method::POST, "/register", None, body).await;
+    assert_eq!(status, StatusCode::OK, "{}", user);
+}
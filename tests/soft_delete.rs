@@ -0,0 +1,67 @@
+mod common;
+
+use gaia_auth::{
+    model::SYSTEM_ORGANIZATION,
+    repository::{GroupRepository, UserRepository},
+};
+use sqlx::PgPool;
+
+#[sqlx::test]
+async fn deleted_rows_are_left_out(db: PgPool) {
+    common::env(&[]);
+    let users = UserRepository::new(db.clone());
+    let groups = GroupRepository::new(db.clone());
+    let group = common::group(&db, "reports", &[]).await;
+    let bob = common::user(&db, "bob", &[group.id]).await;
+
+    assert!(users.delete(SYSTEM_ORGANIZATION, bob.id).await.unwrap());
+    assert!(matches!(
+        users.find(bob.id).await,
+        Err(sqlx::Error::RowNotFound)
+    ));
+    let active = users.find_all(SYSTEM_ORGANIZATION, false).await.unwrap();
+    assert!(active.iter().all(|user| user.id != bob.id));
+    let all = users.find_all(SYSTEM_ORGANIZATION, true).await.unwrap();
+    assert!(all
+        .iter()
+        .any(|user| user.id == bob.id && user.deleted_at.is_some()));
+    assert!(groups.members(group.id).await.unwrap().is_empty());
+    // the membership is kept for restore
+    assert_eq!(users.restored_group_ids(bob.id).await.unwrap(), [group.id]);
+
+    groups.delete(SYSTEM_ORGANIZATION, group.id).await.unwrap();
+    assert!(matches!(
+        groups.find_by_id(SYSTEM_ORGANIZATION, group.id).await,
+        Err(sqlx::Error::RowNotFound)
+    ));
+    assert!(users.restored_group_ids(bob.id).await.unwrap().is_empty());
+}
+
+#[sqlx::test]
+async fn names_of_deleted_rows_can_be_taken(db: PgPool) {
+    common::env(&[]);
+    let users = UserRepository::new(db.clone());
+    let groups = GroupRepository::new(db.clone());
+    let bob = common::user(&db, "bob", &[]).await;
+    let group = common::group(&db, "reports", &[]).await;
+
+    users.delete(SYSTEM_ORGANIZATION, bob.id).await.unwrap();
+    groups.delete(SYSTEM_ORGANIZATION, group.id).await.unwrap();
+    let new_bob = common::user(&db, "bob", &[]).await;
+    let new_group = common::group(&db, "reports", &[]).await;
+    assert_ne!(new_bob.id, bob.id);
+    assert_ne!(new_group.id, group.id);
+}
+
+#[sqlx::test]
+async fn the_last_root_user_is_not_deleted(db: PgPool) {
+    common::env(&[]);
+    let users = UserRepository::new(db.clone());
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let alice = common::user(&db, "alice", &[admins.id]).await;
+    let carol = common::user(&db, "carol", &[admins.id]).await;
+
+    assert!(users.delete(SYSTEM_ORGANIZATION, carol.id).await.unwrap());
+    assert!(!users.delete(SYSTEM_ORGANIZATION, alice.id).await.unwrap());
+    assert!(users.find(alice.id).await.is_ok());
+}
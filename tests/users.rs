@@ -0,0 +1,133 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use gaia_auth::{model::SYSTEM_ORGANIZATION, repository::UserRepository};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+fn ids(users: &Value) -> Vec<Uuid> {
+    users
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|user| user["id"].as_str().unwrap().parse().unwrap())
+        .collect()
+}
+
+#[sqlx::test]
+async fn deleted_users_are_only_listed_to_admins(db: PgPool) {
+    let app = common::app(&db).await;
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    let readers = common::group(&db, "readers", &["user:read"]).await;
+    let reader = common::user(&db, "carol", &[readers.id]).await;
+    let bob = common::user(&db, "bob", &[]).await;
+    let token = Some(admin.token.as_str());
+
+    let uri = format!("/users/{}", bob.id);
+    let (status, _) = common::send(&app, Method::DELETE, &uri, token, None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = common::send(&app, Method::GET, &uri, token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, users) = common::send(&app, Method::GET, "/users", token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!ids(&users).contains(&bob.id));
+    let uri = "/users?include_deleted=true";
+    let (status, users) = common::send(&app, Method::GET, uri, token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(ids(&users).contains(&bob.id));
+
+    let token = Some(reader.token.as_str());
+    let (status, _) = common::send(&app, Method::GET, "/users", token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = common::send(&app, Method::GET, uri, token, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[sqlx::test]
+async fn restoring_a_user_whose_name_was_taken_conflicts(db: PgPool) {
+    let app = common::app(&db).await;
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    let bob = common::user(&db, "bob", &[]).await;
+    let users = UserRepository::new(db.clone());
+    users.delete(SYSTEM_ORGANIZATION, bob.id).await.unwrap();
+    common::user(&db, "bob", &[]).await;
+
+    let uri = format!("/users/{}/restore", bob.id);
+    let token = Some(admin.token.as_str());
+    let (status, body) = common::send(&app, Method::POST, &uri, token, None).await;
+    assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+    assert!(users.find(bob.id).await.is_err());
+
+    // restoring a user that is not deleted
+    let uri = format!("/users/{}/restore", admin.id);
+    let (status, _) = common::send(&app, Method::POST, &uri, token, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[sqlx::test]
+async fn restoring_a_user_needs_its_permissions(db: PgPool) {
+    let app = common::app(&db).await;
+    common::register(&db, &["billing:write"]).await;
+    let managers = common::group(&db, "managers", &["user:delete", "user:read"]).await;
+    let manager = common::user(&db, "alice", &[managers.id]).await;
+    let billing = common::group(&db, "billing", &["billing:write"]).await;
+    let bob = common::user(&db, "bob", &[billing.id]).await;
+    let readers = common::group(&db, "readers", &["user:read"]).await;
+    let carol = common::user(&db, "carol", &[readers.id]).await;
+    let users = UserRepository::new(db.clone());
+    users.delete(SYSTEM_ORGANIZATION, bob.id).await.unwrap();
+    users.delete(SYSTEM_ORGANIZATION, carol.id).await.unwrap();
+    let token = Some(manager.token.as_str());
+
+    let uri = format!("/users/{}/restore", bob.id);
+    let (status, _) = common::send(&app, Method::POST, &uri, token, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(users.find(bob.id).await.is_err());
+
+    let uri = format!("/users/{}/restore", carol.id);
+    let (status, body) = common::send(&app, Method::POST, &uri, token, None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["groups"][0]["id"], readers.id.to_string());
+}
+
+#[sqlx::test]
+async fn the_last_root_user_can_not_be_deleted(db: PgPool) {
+    let app = common::app(&db).await;
+    let admins = common::group(&db, "admins", &["root"]).await;
+    let admin = common::user(&db, "alice", &[admins.id]).await;
+    // the seeded root user is the other active root user
+    sqlx::query("update users set locked = true where username = 'root'")
+        .execute(&db)
+        .await
+        .unwrap();
+
+    let uri = format!("/users/{}", admin.id);
+    let token = Some(admin.token.as_str());
+    let (status, body) = common::send(&app, Method::DELETE, &uri, token, None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "last_root");
+}
+
+#[sqlx::test]
+async fn users_can_set_their_own_password(db: PgPool) {
+    let app = common::app(&db).await;
+    let bob = common::user(&db, "bob", &[]).await;
+    let carol = common::user(&db, "carol", &[]).await;
+    let token = Some(bob.token.as_str());
+    let body = json!({ "password": "correct horse battery staple" });
+
+    let uri = format!("/users/{}/password", carol.id);
+    let (status, _) = common::send(&app, Method::PUT, &uri, token, Some(body.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let uri = format!("/users/{}/password", bob.id);
+    let (status, body) = common::send(&app, Method::PUT, &uri, token, Some(body)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "{}", body);
+
+    let body = json!({ "username": "bob", "password": "correct horse battery staple" });
+    let (status, body) = common::send(&app, Method::POST, "/login", None, Some(body)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}